@@ -0,0 +1,117 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use chunk::{HeightChunk, CHUNK};
+use chunk::gen::{Generator, Terrain};
+use chunk::map::MapImage;
+use chunk::save;
+use chunk::hash;
+
+const USAGE: &str = "usage:
+    worldtool map <out dir> [--world <dir> | --seed <n> (--radius <chunks> | --count <n>)] [--tile <chunks>]
+    worldtool hash [--world <dir> | --seed <n> (--radius <chunks> | --count <n>)]";
+
+enum Source {
+    Saved(PathBuf),
+    Generated(Box<Terrain>)
+}
+impl Source {
+    fn chunk(&self, cx: i32, cz: i32) -> Option<HeightChunk> {
+        match self {
+            Source::Saved(dir) => save::read_chunk(dir, cx, cz).unwrap_or_else(|e| fail(&format!("chunk {} {}: {}", cx, cz, e))),
+            Source::Generated(terrain) => Some(terrain.generate(cx, cz))
+        }
+    }
+}
+
+fn fail(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    exit(1)
+}
+
+fn flag<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter().position(|a| a == name).and_then(|i| args.get(i + 1)).map(|s| s.as_str())
+}
+
+fn number<T: std::str::FromStr>(args: &[String], name: &str, default: T) -> T {
+    match flag(args, name) {
+        Some(v) => v.parse().unwrap_or_else(|_| fail(&format!("{} expects a number", name))),
+        None => default
+    }
+}
+
+// Saved worlds list their own chunks. Generated ones cover the square from -radius to radius
+// chunks around the origin, or with --count the first n chunks of a square growing from
+// (0, 0) row by row.
+fn source(args: &[String]) -> (Source, Vec<(i32, i32)>) {
+    if let Some(dir) = flag(args, "--world") {
+        let chunks = save::list_chunks(Path::new(dir)).unwrap_or_else(|e| fail(&format!("{}: {}", dir, e)));
+        return (Source::Saved(dir.into()), chunks)
+    }
+    let chunks = match flag(args, "--count") {
+        Some(_) => {
+            let n: i32 = number(args, "--count", 0);
+            let side = (n as f64).sqrt().ceil() as i32;
+            (0..n).map(|i| (i % side, i / side)).collect()
+        },
+        None => {
+            let r: i32 = number(args, "--radius", 1);
+            (-r..=r).flat_map(|cx| (-r..=r).map(move |cz| (cx, cz))).collect()
+        }
+    };
+    return (Source::Generated(Box::new(Terrain::new(number(args, "--seed", 0)))), chunks)
+}
+
+fn map(out: &Path, args: &[String]) {
+    let (source, chunks) = source(args);
+    let tile: i32 = number(args, "--tile", 4);
+    if tile <= 0 {
+        fail("--tile must be positive")
+    }
+    std::fs::create_dir_all(out).unwrap_or_else(|e| fail(&format!("{}: {}", out.display(), e)));
+
+    let mut tiles = BTreeMap::<(i32, i32), Vec<(i32, i32)>>::new();
+    for (cx, cz) in chunks {
+        tiles.entry((cx.div_euclid(tile), cz.div_euclid(tile))).or_default().push((cx, cz));
+    }
+
+    for ((tx, tz), chunks) in tiles {
+        let size = tile as usize * CHUNK;
+        let mut img = MapImage::new(size, size);
+        for (cx, cz) in chunks {
+            if let Some(chunk) = source.chunk(cx, cz) {
+                img.draw_chunk((cx - tx * tile) as usize * CHUNK, (cz - tz * tile) as usize * CHUNK, &chunk);
+            }
+        }
+        let path = out.join(format!("tile.{}.{}.png", tx, tz));
+        img.write_png(&path).unwrap_or_else(|e| fail(&format!("{}: {}", path.display(), e)));
+        println!("{}", path.display());
+    }
+}
+
+fn hash(args: &[String]) {
+    let (source, chunks) = source(args);
+    let mut regions = BTreeMap::<(i32, i32), Vec<((i32, i32), u64)>>::new();
+    for (cx, cz) in chunks {
+        if let Some(chunk) = source.chunk(cx, cz) {
+            let h = chunk.content_hash();
+            println!("chunk {} {} {:016x}", cx, cz, h);
+            regions.entry(hash::region_of(cx, cz)).or_default().push(((cx, cz), h));
+        }
+    }
+    for ((rx, rz), chunks) in regions {
+        println!("region {} {} {:016x}", rx, rz, hash::region_hash(&chunks));
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(|s| s.as_str()) {
+        Some("map") => match args.get(1) {
+            Some(out) => map(Path::new(out), &args[2..]),
+            None => fail(USAGE)
+        },
+        Some("hash") => hash(&args[1..]),
+        _ => fail(USAGE)
+    }
+}
@@ -0,0 +1,130 @@
+use crate::breaking::Tool;
+use crate::item::Item;
+
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Block {
+    Air,
+    Stone,
+    Dirt,
+    Grass,
+    Sand,
+    Water,
+    Portal,
+    Lava,
+    Gravel,
+    Crystal,
+    Ladder,
+    Vine
+}
+impl Block {
+    pub const ALL: &'static [Block] = &[Block::Air, Block::Stone, Block::Dirt, Block::Grass, Block::Sand, Block::Water, Block::Portal, Block::Lava, Block::Gravel, Block::Crystal, Block::Ladder, Block::Vine];
+
+    pub fn from_id(id: u8) -> Option<Block> {
+        Self::ALL.get(id as usize).copied()
+    }
+
+    // The name saves and commands refer to the block by
+    pub fn name(self) -> &'static str {
+        match self {
+            Block::Air => "air",
+            Block::Stone => "stone",
+            Block::Dirt => "dirt",
+            Block::Grass => "grass",
+            Block::Sand => "sand",
+            Block::Water => "water",
+            Block::Portal => "portal",
+            Block::Lava => "lava",
+            Block::Gravel => "gravel",
+            Block::Crystal => "crystal",
+            Block::Ladder => "ladder",
+            Block::Vine => "vine"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Block> {
+        Self::ALL.iter().copied().find(|b| b.name() == name)
+    }
+
+    pub fn is_solid(self) -> bool {
+        !matches!(self, Block::Air | Block::Water | Block::Portal | Block::Lava | Block::Ladder | Block::Vine)
+    }
+    pub fn is_liquid(self) -> bool {
+        self == Block::Water || self == Block::Lava
+    }
+    // Red, green and blue light given off, each up to light::MAX_LIGHT
+    pub fn emission(self) -> [u8; 3] {
+        match self {
+            Block::Lava => [15, 8, 2],
+            Block::Portal => [8, 3, 11],
+            Block::Crystal => [3, 8, 15],
+            _ => [0, 0, 0]
+        }
+    }
+    // Health lost each time a body touching the block is hurt by it, see health::Health::touch
+    pub fn damage(self) -> f32 {
+        match self {
+            Block::Lava => 4.,
+            _ => 0.
+        }
+    }
+    // How long the block takes to break, see breaking::Tool::break_time. None for blocks
+    // that can't be broken.
+    pub fn hardness(self) -> Option<f32> {
+        match self {
+            Block::Air | Block::Water | Block::Lava | Block::Portal => None,
+            Block::Stone => Some(1.5),
+            Block::Dirt | Block::Sand => Some(0.5),
+            Block::Grass | Block::Gravel => Some(0.6),
+            Block::Crystal => Some(0.3),
+            Block::Ladder => Some(0.4),
+            Block::Vine => Some(0.)
+        }
+    }
+    // What is left behind when the block is broken
+    pub fn drop(self) -> Option<Item> {
+        match self {
+            Block::Air | Block::Water | Block::Lava | Block::Portal => None,
+            Block::Grass => Some(Item::Block(Block::Dirt)),
+            b => Some(Item::Block(b))
+        }
+    }
+    // The tool that breaks the block fastest
+    pub fn tool(self) -> Option<Tool> {
+        match self {
+            Block::Stone | Block::Crystal => Some(Tool::Pickaxe),
+            Block::Dirt | Block::Grass | Block::Sand | Block::Gravel => Some(Tool::Shovel),
+            _ => None
+        }
+    }
+    // Blocks bodies can climb up and down while they are inside them
+    pub fn climbable(self) -> bool {
+        self == Block::Ladder || self == Block::Vine
+    }
+    // Blocks the crosshair stops at, so they can be broken: everything solid, and the
+    // climbable blocks that can be walked through
+    pub fn targetable(self) -> bool {
+        self.is_solid() || self.climbable()
+    }
+    // Blocks that fall when there is nothing solid below them
+    pub fn falls(self) -> bool {
+        self == Block::Sand || self == Block::Gravel
+    }
+
+    pub fn color(self) -> [f32; 3] {
+        match self {
+            Block::Air => [0., 0., 0.],
+            Block::Stone => [0.5, 0.5, 0.5],
+            Block::Dirt => [0.45, 0.3, 0.15],
+            Block::Grass => [0.3, 0.65, 0.2],
+            Block::Sand => [0.85, 0.8, 0.5],
+            Block::Water => [0.15, 0.35, 0.8],
+            Block::Portal => [0.55, 0.2, 0.8],
+            Block::Lava => [0.9, 0.4, 0.05],
+            Block::Gravel => [0.55, 0.5, 0.48],
+            Block::Crystal => [0.45, 0.75, 1.0],
+            Block::Ladder => [0.6, 0.45, 0.25],
+            Block::Vine => [0.2, 0.5, 0.15]
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use crate::Block;
+
+// Stages the crack overlay goes through while a block is being broken
+pub const STAGES: u8 = 10;
+// Seconds per point of hardness with the wrong tool, or none
+const SECONDS_PER_HARDNESS: f32 = 1.5;
+// How much faster the right tool for a block breaks it
+const TOOL_SPEED: f32 = 4.;
+
+type Pos = (usize, usize, usize);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Tool {
+    Hand,
+    Pickaxe,
+    Shovel
+}
+impl Tool {
+    pub const ALL: [Tool; 3] = [Tool::Hand, Tool::Pickaxe, Tool::Shovel];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Tool::Hand => "hand",
+            Tool::Pickaxe => "pickaxe",
+            Tool::Shovel => "shovel"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Tool> {
+        Self::ALL.iter().copied().find(|t| t.name() == name)
+    }
+
+    // Seconds it takes to break block with this tool, None if it can't be broken
+    pub fn break_time(self, block: Block) -> Option<f32> {
+        let speed = if block.tool() == Some(self) { TOOL_SPEED } else { 1. };
+        return block.hardness().map(|h| h * SECONDS_PER_HARDNESS / speed)
+    }
+}
+
+// Progress on the block being broken. Looking at a different block starts over.
+#[derive(Default)]
+pub struct Breaking {
+    pub target: Option<Pos>,
+    // 0 when just started, 1 when broken
+    pub progress: f32
+}
+impl Breaking {
+    pub fn new() -> Self {
+        Self { target: None, progress: 0. }
+    }
+
+    // Works on block at target for dt seconds. Returns whether it broke.
+    pub fn hit(&mut self, target: Pos, block: Block, tool: Tool, dt: f32) -> bool {
+        if self.target != Some(target) {
+            self.target = Some(target);
+            self.progress = 0.;
+        }
+        let time = match tool.break_time(block) {
+            Some(t) => t,
+            None => return false
+        };
+        self.progress = if time <= 0. { 1. } else { self.progress + dt / time };
+        if self.progress >= 1. {
+            self.stop();
+            return true
+        }
+        return false
+    }
+
+    pub fn stop(&mut self) {
+        self.target = None;
+        self.progress = 0.;
+    }
+
+    // Which crack stage to draw over the target, from 0 to STAGES - 1
+    pub fn stage(&self) -> Option<(Pos, u8)> {
+        let stage = ((self.progress * STAGES as f32) as u8).min(STAGES - 1);
+        return self.target.map(|t| (t, stage))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: f32 = 1. / 60.;
+
+    // Ticks of hitting block until it breaks
+    fn ticks_to_break(block: Block, tool: Tool) -> usize {
+        let mut breaking = Breaking::new();
+        for n in 1..10000 {
+            if breaking.hit((1, 2, 3), block, tool, DT) {
+                return n
+            }
+        }
+        panic!("{} never broke", block.name())
+    }
+
+    #[test]
+    fn break_time_scales_with_hardness_and_tool() {
+        assert_eq!(Tool::Hand.break_time(Block::Stone), Some(1.5 * SECONDS_PER_HARDNESS));
+        assert_eq!(Tool::Pickaxe.break_time(Block::Stone), Some(1.5 * SECONDS_PER_HARDNESS / TOOL_SPEED));
+        // The wrong tool is no better than a hand
+        assert_eq!(Tool::Shovel.break_time(Block::Stone), Tool::Hand.break_time(Block::Stone));
+        assert_eq!(Tool::Hand.break_time(Block::Water), None);
+
+        assert!(ticks_to_break(Block::Dirt, Tool::Hand) < ticks_to_break(Block::Stone, Tool::Hand));
+        assert_eq!(ticks_to_break(Block::Stone, Tool::Hand), 135);
+        assert_eq!(ticks_to_break(Block::Stone, Tool::Pickaxe), 34);
+    }
+
+    #[test]
+    fn progress_starts_over_on_a_new_target() {
+        let mut breaking = Breaking::new();
+        for _ in 0..60 {
+            breaking.hit((1, 2, 3), Block::Stone, Tool::Hand, DT);
+        }
+        assert!(breaking.progress > 0.4);
+        assert!(!breaking.hit((1, 2, 4), Block::Stone, Tool::Hand, DT));
+        assert_eq!(breaking.target, Some((1, 2, 4)));
+        assert!(breaking.progress < 0.01);
+    }
+
+    #[test]
+    fn stages_follow_progress() {
+        let mut breaking = Breaking::new();
+        assert_eq!(breaking.stage(), None);
+        breaking.target = Some((1, 2, 3));
+        for (progress, stage) in [(0., 0), (0.09, 0), (0.1, 1), (0.55, 5), (0.999, STAGES - 1), (1., STAGES - 1)] {
+            breaking.progress = progress;
+            assert_eq!(breaking.stage(), Some(((1, 2, 3), stage)), "at {}", progress);
+        }
+    }
+
+    #[test]
+    fn zero_hardness_breaks_at_once() {
+        assert_eq!(Block::Vine.hardness(), Some(0.));
+        assert_eq!(ticks_to_break(Block::Vine, Tool::Hand), 1);
+        let mut breaking = Breaking::new();
+        assert!(!breaking.hit((1, 2, 3), Block::Portal, Tool::Pickaxe, 100.));
+    }
+}
@@ -0,0 +1,263 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use crate::item::{Item, ItemStack};
+
+// Recipes are text files of "key value..." lines, with blocks and items named as in saves:
+//
+//   # a comment
+//   shaped ladder 3
+//   row v.v
+//   row vvv
+//   row v.v
+//   key v vine
+//
+//   shapeless gravel
+//   ingredients stone
+//
+// Each "shaped" or "shapeless" line starts a recipe making count (default 1) of the item.
+// Shaped rows use one character per cell, '.' for an empty one, and match anywhere in the
+// grid. Shapeless ingredients match in any cells, one item per cell.
+
+// Crafting grids are at most this wide and tall
+pub const GRID: usize = 3;
+
+fn invalid(line: usize, msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", line, msg))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Shape {
+    // Cells row by row, width across
+    Shaped { width: usize, cells: Vec<Option<Item>> },
+    Shapeless(Vec<Item>)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recipe {
+    pub shape: Shape,
+    pub result: ItemStack
+}
+impl Recipe {
+    // Whether the items in grid, a square width cells across, make this recipe
+    pub fn matches(&self, grid: &[Option<ItemStack>], width: usize) -> bool {
+        let item = |x: usize, y: usize| grid[y * width + x].map(|s| s.item);
+        match &self.shape {
+            Shape::Shaped { width: w, cells } => {
+                let (w, h) = (*w, cells.len() / w);
+                let height = grid.len() / width;
+                // Every other cell of the grid has to be empty wherever the recipe sits
+                let fits = |ox: usize, oy: usize| (0..height).all(|y| (0..width).all(|x| {
+                    let inside = x >= ox && x < ox + w && y >= oy && y < oy + h;
+                    item(x, y) == if inside { cells[(y - oy) * w + x - ox] } else { None }
+                }));
+                return w <= width && h <= height && (0..=height - h).any(|oy| (0..=width - w).any(|ox| fits(ox, oy)))
+            },
+            Shape::Shapeless(ingredients) => {
+                let mut have: Vec<&str> = grid.iter().flatten().map(|s| s.item.name()).collect();
+                let mut want: Vec<&str> = ingredients.iter().map(|i| i.name()).collect();
+                have.sort();
+                want.sort();
+                return have == want
+            }
+        }
+    }
+}
+
+// A recipe while its lines are being read
+struct Draft<'a> {
+    line: usize,
+    shaped: bool,
+    result: ItemStack,
+    rows: Vec<&'a str>,
+    keys: Vec<(char, Item)>,
+    ingredients: Vec<Item>
+}
+impl Draft<'_> {
+    fn finish(self) -> io::Result<Recipe> {
+        let (n, result) = (self.line, self.result);
+        if !self.shaped {
+            if self.ingredients.is_empty() || self.ingredients.len() > GRID * GRID {
+                return Err(invalid(n, "a shapeless recipe needs 1 to 9 ingredients"))
+            }
+            return Ok(Recipe { shape: Shape::Shapeless(self.ingredients), result })
+        }
+        let width = self.rows.first().map(|r| r.chars().count()).unwrap_or(0);
+        if self.rows.is_empty() || self.rows.len() > GRID || width > GRID || self.rows.iter().any(|r| r.chars().count() != width) {
+            return Err(invalid(n, "a shaped recipe needs 1 to 3 rows of the same length, up to 3 long"))
+        }
+        let mut cells = Vec::new();
+        for c in self.rows.iter().flat_map(|r| r.chars()) {
+            cells.push(match c {
+                '.' => None,
+                c => Some(self.keys.iter().find(|k| k.0 == c).ok_or_else(|| invalid(n, &format!("no key for {}", c)))?.1)
+            });
+        }
+        if cells.iter().all(|c| c.is_none()) {
+            return Err(invalid(n, "a shaped recipe needs an ingredient"))
+        }
+        return Ok(Recipe { shape: Shape::Shaped { width, cells }, result })
+    }
+}
+
+// All the recipes the game knows
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RecipeBook {
+    pub recipes: Vec<Recipe>
+}
+impl RecipeBook {
+    pub fn from_text(text: &str) -> io::Result<RecipeBook> {
+        let mut book = RecipeBook::default();
+        let mut draft: Option<Draft> = None;
+        for (i, line) in text.lines().enumerate() {
+            let n = i + 1;
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let item = |name: &str| Item::from_name(name).ok_or_else(|| invalid(n, &format!("unknown item {}", name)));
+            match words[..] {
+                [] => {},
+                [w, ..] if w.starts_with('#') => {},
+                [kind @ ("shaped" | "shapeless"), result, ref count @ ..] => {
+                    if let Some(done) = draft.take() {
+                        book.recipes.push(done.finish()?);
+                    }
+                    let count = match count {
+                        [] => 1,
+                        [c] => c.parse::<u32>().ok().filter(|c| *c > 0).ok_or_else(|| invalid(n, "bad count"))?,
+                        _ => return Err(invalid(n, "too many words"))
+                    };
+                    let result = ItemStack::new(item(result)?, count);
+                    draft = Some(Draft { line: n, shaped: kind == "shaped", result, rows: Vec::new(), keys: Vec::new(), ingredients: Vec::new() });
+                },
+                ["row", cells] => match &mut draft {
+                    Some(d) if d.shaped => d.rows.push(cells),
+                    _ => return Err(invalid(n, "row outside a shaped recipe"))
+                },
+                ["key", key, name] if key.chars().count() == 1 && key != "." => match &mut draft {
+                    Some(d) if d.shaped => d.keys.push((key.chars().next().unwrap(), item(name)?)),
+                    _ => return Err(invalid(n, "key outside a shaped recipe"))
+                },
+                ["ingredients", ref names @ ..] => match &mut draft {
+                    Some(d) if !d.shaped => for name in names {
+                        d.ingredients.push(item(name)?);
+                    },
+                    _ => return Err(invalid(n, "ingredients outside a shapeless recipe"))
+                },
+                _ => return Err(invalid(n, "unknown line"))
+            }
+        }
+        if let Some(done) = draft {
+            book.recipes.push(done.finish()?);
+        }
+        return Ok(book)
+    }
+
+    // Reads every .txt file in dir, in name order
+    pub fn load_dir(dir: &Path) -> io::Result<RecipeBook> {
+        let entries = fs::read_dir(dir).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", dir.display(), e)))?;
+        let mut paths = entries.map(|e| e.map(|e| e.path())).collect::<io::Result<Vec<_>>>()?;
+        paths.retain(|p| p.extension().is_some_and(|e| e == "txt"));
+        paths.sort();
+        let mut book = RecipeBook::default();
+        for path in paths {
+            let text = fs::read_to_string(&path)?;
+            let more = Self::from_text(&text).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+            book.recipes.extend(more.recipes);
+        }
+        return Ok(book)
+    }
+
+    // The first recipe the grid makes
+    pub fn find(&self, grid: &[Option<ItemStack>], width: usize) -> Option<&Recipe> {
+        self.recipes.iter().find(|r| r.matches(grid, width))
+    }
+
+    // Uses up one item from each filled cell and returns what they make
+    pub fn craft(&self, grid: &mut [Option<ItemStack>], width: usize) -> Option<ItemStack> {
+        let result = self.find(grid, width)?.result;
+        for cell in grid.iter_mut() {
+            if let Some(stack) = cell {
+                stack.count -= 1;
+                if stack.count == 0 {
+                    *cell = None;
+                }
+            }
+        }
+        return Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Block;
+
+    const TEXT: &str = "
+# two stone side by side make gravel
+shaped gravel 4
+row ss
+key s stone
+
+shapeless grass
+ingredients dirt vine vine
+";
+
+    fn grid(cells: &[(usize, Block)]) -> Vec<Option<ItemStack>> {
+        let mut grid = vec![None; GRID * GRID];
+        for (i, b) in cells {
+            grid[*i] = Some(ItemStack::new(Item::Block(*b), 1));
+        }
+        return grid
+    }
+
+    #[test]
+    fn parses_shaped_and_shapeless_recipes() {
+        let book = RecipeBook::from_text(TEXT).unwrap();
+        let stone = Some(Item::Block(Block::Stone));
+        assert_eq!(book.recipes, vec![
+            Recipe { shape: Shape::Shaped { width: 2, cells: vec![stone, stone] }, result: ItemStack::new(Item::Block(Block::Gravel), 4) },
+            Recipe { shape: Shape::Shapeless(vec![Item::Block(Block::Dirt), Item::Block(Block::Vine), Item::Block(Block::Vine)]), result: ItemStack::new(Item::Block(Block::Grass), 1) }
+        ]);
+    }
+
+    #[test]
+    fn bad_recipes_are_errors() {
+        for text in ["row ss", "shaped gravel\nrow sx\nkey s stone", "shaped gravel\nrow s\nrow ss\nkey s stone", "shapeless water\ningredients stone", "shapeless gravel"] {
+            assert_eq!(RecipeBook::from_text(text).unwrap_err().kind(), io::ErrorKind::InvalidData, "{}", text);
+        }
+    }
+
+    #[test]
+    fn shaped_recipes_match_anywhere_in_the_grid() {
+        let book = RecipeBook::from_text(TEXT).unwrap();
+        let made = |cells: &[(usize, Block)]| book.find(&grid(cells), GRID).map(|r| r.result.item);
+        let gravel = Some(Item::Block(Block::Gravel));
+        assert_eq!(made(&[(0, Block::Stone), (1, Block::Stone)]), gravel);
+        assert_eq!(made(&[(4, Block::Stone), (5, Block::Stone)]), gravel);
+        assert_eq!(made(&[(7, Block::Stone), (8, Block::Stone)]), gravel);
+        // Split across rows, stacked or with something else in the grid
+        assert_eq!(made(&[(2, Block::Stone), (3, Block::Stone)]), None);
+        assert_eq!(made(&[(0, Block::Stone), (3, Block::Stone)]), None);
+        assert_eq!(made(&[(0, Block::Stone), (1, Block::Stone), (8, Block::Dirt)]), None);
+    }
+
+    #[test]
+    fn shapeless_recipes_match_in_any_order() {
+        let book = RecipeBook::from_text(TEXT).unwrap();
+        let made = |cells: &[(usize, Block)]| book.find(&grid(cells), GRID).map(|r| r.result.item);
+        let grass = Some(Item::Block(Block::Grass));
+        assert_eq!(made(&[(0, Block::Dirt), (1, Block::Vine), (2, Block::Vine)]), grass);
+        assert_eq!(made(&[(8, Block::Vine), (3, Block::Dirt), (4, Block::Vine)]), grass);
+        assert_eq!(made(&[(0, Block::Dirt), (1, Block::Vine)]), None);
+        assert_eq!(made(&[(0, Block::Dirt), (1, Block::Vine), (2, Block::Vine), (5, Block::Vine)]), None);
+    }
+
+    #[test]
+    fn crafting_uses_one_item_per_cell() {
+        let book = RecipeBook::from_text(TEXT).unwrap();
+        let mut cells = grid(&[(0, Block::Stone), (1, Block::Stone)]);
+        cells[0] = Some(ItemStack::new(Item::Block(Block::Stone), 2));
+        assert_eq!(book.craft(&mut cells, GRID), Some(ItemStack::new(Item::Block(Block::Gravel), 4)));
+        assert_eq!(cells, grid(&[(0, Block::Stone)]));
+        assert_eq!(book.craft(&mut cells, GRID), None);
+    }
+}
@@ -0,0 +1,182 @@
+use cgmath::{InnerSpace, Point3, Vector3};
+use crate::{HeightChunk, HEIGHT};
+use crate::item::ItemStack;
+use crate::mesh::{self, FaceInstance};
+use crate::physics::Body;
+
+pub const ITEM_SIZE: f32 = 0.25;
+// Players pick up items this close to the middle of their body
+pub const PICKUP_RANGE: f32 = 1.5;
+// Seconds before a dropped item can be picked up, so it is seen flying out first
+const PICKUP_DELAY: f32 = 0.5;
+// Items of the same kind this close together become one
+const MERGE_RANGE: f32 = 0.5;
+// Items nobody picks up disappear after this many seconds
+const LIFETIME: f32 = 300.;
+const BOB_HEIGHT: f32 = 0.1;
+const BOB_SPEED: f32 = 3.;
+// Speed an item flies out with when a block breaks
+const POP: f32 = 3.;
+
+pub struct ItemEntity {
+    pub stack: ItemStack,
+    pub body: Body,
+    // Seconds since it was dropped
+    pub age: f32
+}
+impl ItemEntity {
+    pub fn faces(&self, chunk: &HeightChunk) -> Vec<FaceInstance> {
+        let p = self.body.pos;
+        let c = HeightChunk::to_index(Point3::new(p.x, p.y.max(0.), p.z));
+        let c = (c.0, c.1.min(HEIGHT - 1), c.2);
+        let light = (chunk.sky_light(c), chunk.block_light(c));
+        let y = p.y + ITEM_SIZE / 2. + BOB_HEIGHT * (1. + (self.age * BOB_SPEED).sin());
+        return (0..6).map(|f| mesh::colored_face([p.x, y, p.z], f, self.stack.item.color(), 1., light, mesh::NO_OCCLUSION, ITEM_SIZE)).collect()
+    }
+}
+
+// Everything in a dimension that moves on its own, apart from falling blocks
+#[derive(Default)]
+pub struct Entities {
+    pub items: Vec<ItemEntity>
+}
+impl Entities {
+    pub fn new() -> Self {
+        Self { items: Vec::new() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    // Drops stack at the middle of block c, popping up and out in a direction picked by r
+    pub fn drop_item(&mut self, c: (usize, usize, usize), stack: ItemStack, r: u64) {
+        let mut body = Body::cube(Point3::new(c.0 as f32, c.1 as f32 - ITEM_SIZE / 2., c.2 as f32), ITEM_SIZE);
+        let angle = (r % 360) as f32 * std::f32::consts::PI / 180.;
+        body.vel = Vector3::new(angle.cos() * POP / 2., POP, angle.sin() * POP / 2.);
+        self.items.push(ItemEntity { stack, body, age: 0. });
+    }
+
+    pub fn update(&mut self, chunk: &HeightChunk, dt: f32) {
+        for item in self.items.iter_mut() {
+            item.age += dt;
+            item.body.apply_gravity(dt);
+            if item.body.on_ground {
+                item.body.vel.x = 0.;
+                item.body.vel.z = 0.;
+            }
+            item.body.step(chunk, dt);
+        }
+        self.items.retain(|item| item.age < LIFETIME);
+        self.merge();
+    }
+
+    // Folds items into an earlier one of the same kind close by, while it has room
+    fn merge(&mut self) {
+        let mut i = 0;
+        while i < self.items.len() {
+            let mut j = i + 1;
+            while j < self.items.len() {
+                let (a, b) = (&self.items[i], &self.items[j]);
+                let close = (a.body.pos - b.body.pos).magnitude() < MERGE_RANGE;
+                if close && a.stack.item == b.stack.item && a.stack.count + b.stack.count <= a.stack.item.max_stack() {
+                    let b = self.items.remove(j);
+                    self.items[i].stack.count += b.stack.count;
+                    self.items[i].age = self.items[i].age.min(b.age);
+                } else {
+                    j += 1;
+                }
+            }
+            i += 1;
+        }
+    }
+
+    // Takes the items within reach of a player, handing each to take. Whatever take gives
+    // back stays on the ground.
+    pub fn pick_up(&mut self, player: &Body, mut take: impl FnMut(ItemStack) -> Option<ItemStack>) {
+        let centre = player.pos + Vector3::new(0., player.height / 2., 0.);
+        for item in self.items.iter_mut() {
+            if item.age < PICKUP_DELAY || (item.body.pos - centre).magnitude() > PICKUP_RANGE {
+                continue
+            }
+            match take(item.stack) {
+                Some(left) => item.stack = left,
+                None => item.stack.count = 0
+            }
+        }
+        self.items.retain(|item| item.stack.count > 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Block;
+    use crate::item::Item;
+    use crate::inventory::{Inventory, SLOTS};
+
+    fn stack(block: Block, count: u32) -> ItemStack {
+        ItemStack::new(Item::Block(block), count)
+    }
+
+    // Items lying at x along a line, old enough to be picked up
+    fn lying(items: &[(f32, ItemStack)]) -> Entities {
+        let items = items.iter().map(|(x, stack)| ItemEntity { stack: *stack, body: Body::cube(Point3::new(*x, 1., 8.), ITEM_SIZE), age: 1. });
+        return Entities { items: items.collect() }
+    }
+
+    fn counts(entities: &Entities) -> Vec<u32> {
+        entities.items.iter().map(|i| i.stack.count).collect()
+    }
+
+    #[test]
+    fn close_items_of_a_kind_merge_while_they_fit() {
+        let mut entities = lying(&[(8., stack(Block::Dirt, 3)), (8.2, stack(Block::Dirt, 2)), (8.1, stack(Block::Sand, 1)), (10., stack(Block::Dirt, 1))]);
+        entities.merge();
+        assert_eq!(counts(&entities), vec![5, 1, 1]);
+        assert_eq!(entities.items[1].stack.item, Item::Block(Block::Sand));
+
+        let max = Item::Block(Block::Dirt).max_stack();
+        let mut full = lying(&[(8., stack(Block::Dirt, max - 1)), (8., stack(Block::Dirt, 2))]);
+        full.merge();
+        assert_eq!(counts(&full), vec![max - 1, 2]);
+    }
+
+    #[test]
+    fn players_pick_up_items_within_range() {
+        let player = Body::player(Point3::new(8., 0.5, 8.));
+        let mut entities = lying(&[(8. + PICKUP_RANGE * 0.9, stack(Block::Dirt, 1)), (8. + PICKUP_RANGE * 1.1, stack(Block::Sand, 1))]);
+        let mut taken = Vec::new();
+        entities.pick_up(&player, |s| {
+            taken.push(s);
+            None
+        });
+        assert_eq!(taken, vec![stack(Block::Dirt, 1)]);
+        assert_eq!(entities.items.len(), 1);
+
+        // Not while they are still flying out
+        let mut fresh = lying(&[(8., stack(Block::Dirt, 1))]);
+        fresh.items[0].age = 0.;
+        fresh.pick_up(&player, |_| None);
+        assert_eq!(fresh.items.len(), 1);
+    }
+
+    #[test]
+    fn what_does_not_fit_stays_on_the_ground() {
+        let player = Body::player(Point3::new(8., 0.5, 8.));
+        let mut entities = lying(&[(8., stack(Block::Dirt, 5)), (8.5, stack(Block::Stone, 2))]);
+        // Room for three of the dirt and none of the stone
+        entities.pick_up(&player, |s| match s.item {
+            Item::Block(Block::Dirt) => Some(ItemStack::new(s.item, s.count - 3)),
+            _ => Some(s)
+        });
+        assert_eq!(counts(&entities), vec![2, 2]);
+
+        let mut inventory = Inventory::new();
+        for _ in 0..SLOTS {
+            inventory.add(stack(Block::Sand, Item::Block(Block::Sand).max_stack()));
+        }
+        entities.pick_up(&player, |s| inventory.add(s));
+        assert_eq!(counts(&entities), vec![2, 2]);
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::BTreeSet;
+use crate::{Block, HeightChunk, HEIGHT};
+use crate::mesh::{self, FaceInstance};
+
+const GRAVITY: f32 = 20.;
+const TERMINAL: f32 = 20.;
+
+type Pos = (usize, usize, usize);
+
+// A block that lost its support, taken out of the chunk until it lands
+pub struct FallingBlock {
+    pub block: Block,
+    pub x: usize,
+    pub z: usize,
+    pub y: f32,
+    pub vel: f32
+}
+impl FallingBlock {
+    // Lit by the light of the block it is passing through
+    pub fn faces(&self, chunk: &HeightChunk) -> Vec<FaceInstance> {
+        let c = (self.x, self.y.round().max(0.) as usize, self.z);
+        let light = (chunk.sky_light(c), chunk.block_light(c));
+        return (0..6).map(|f| mesh::face([self.x as f32, self.y, self.z as f32], f, self.block, 1., light, mesh::NO_OCCLUSION)).collect()
+    }
+}
+
+fn supported(chunk: &HeightChunk, c: Pos) -> bool {
+    c.1 == 0 || !chunk.is_empty_raw((c.0, c.1 - 1, c.2))
+}
+
+#[derive(Default)]
+pub struct FallingBlocks {
+    pending: BTreeSet<Pos>,
+    pub entities: Vec<FallingBlock>,
+    // Blocks that came down on something they can't replace, such as a ladder or water,
+    // waiting to be dropped there as items
+    pub blocked: Vec<(Pos, Block)>
+}
+impl FallingBlocks {
+    pub fn new() -> Self {
+        Self { pending: BTreeSet::new(), entities: Vec::new(), blocked: Vec::new() }
+    }
+
+    // Call after changing a block; the block above it may have lost its support
+    pub fn notify(&mut self, c: Pos) {
+        self.pending.insert(c);
+        if c.1 + 1 < HEIGHT {
+            self.pending.insert((c.0, c.1 + 1, c.2));
+        }
+    }
+
+    // Lowest free position a block falling from y would come to rest at
+    fn landing(chunk: &HeightChunk, x: usize, y: usize, z: usize) -> usize {
+        let mut y = y;
+        while !chunk.is_empty_raw((x, y, z)) && y + 1 < HEIGHT {
+            y += 1;
+        }
+        while !supported(chunk, (x, y, z)) {
+            y -= 1;
+        }
+        return y
+    }
+
+    // Releases unsupported blocks and moves the falling ones. Each block that starts falling
+    // checks the one above it in turn, so a whole tower comes down one block per update.
+    // Returns the positions that changed in the chunk.
+    pub fn update(&mut self, chunk: &mut HeightChunk, delta: f32) -> Vec<Pos> {
+        let mut changed = Vec::new();
+
+        for c in std::mem::take(&mut self.pending) {
+            let block = match chunk.get(c) {
+                Some(b) if b.falls() => *b,
+                _ => continue
+            };
+            if supported(chunk, c) {
+                continue
+            }
+            chunk.set(c, Block::Air, 0);
+            self.entities.push(FallingBlock { block, x: c.0, z: c.2, y: c.1 as f32, vel: 0. });
+            self.notify(c);
+            changed.push(c);
+        }
+
+        let mut landed = Vec::new();
+        for (i, f) in self.entities.iter_mut().enumerate() {
+            f.vel = (f.vel + GRAVITY * delta).min(TERMINAL);
+            let target = Self::landing(chunk, f.x, f.y.floor() as usize, f.z);
+            f.y -= f.vel * delta;
+            if f.y <= target as f32 {
+                landed.push(i);
+            }
+        }
+        for i in landed.into_iter().rev() {
+            let f = self.entities.remove(i);
+            changed.extend(self.land(chunk, f));
+        }
+        return changed
+    }
+
+    // Only air is landed in. Returns the position if the block was placed.
+    fn land(&mut self, chunk: &mut HeightChunk, f: FallingBlock) -> Option<Pos> {
+        let c = (f.x, Self::landing(chunk, f.x, f.y.max(0.).ceil() as usize, f.z), f.z);
+        if *chunk.get(c).unwrap() != Block::Air {
+            self.blocked.push((c, f.block));
+            return None
+        }
+        chunk.set(c, f.block, 0);
+        self.notify(c);
+        return Some(c)
+    }
+
+    // Puts every falling block straight down where it would land, for when the chunk stops
+    // being updated. Returns the positions that changed in the chunk.
+    pub fn land_all(&mut self, chunk: &mut HeightChunk) -> Vec<Pos> {
+        let falling = std::mem::take(&mut self.entities);
+        return falling.into_iter().filter_map(|f| self.land(chunk, f)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn floor() -> HeightChunk {
+        HeightChunk::for_each(|_, y, _| if y == 0 { Block::Stone } else { Block::Air })
+    }
+
+    // Updates at 60 steps a second until every block has landed
+    fn settle(chunk: &mut HeightChunk, falling: &mut FallingBlocks) {
+        let mut steps = 0;
+        while !falling.pending.is_empty() || !falling.entities.is_empty() {
+            falling.update(chunk, 1. / 60.);
+            steps += 1;
+            assert!(steps < 10000, "blocks never landed");
+        }
+    }
+
+    #[test]
+    fn tower_collapses_onto_the_floor() {
+        let mut chunk = floor();
+        let mut falling = FallingBlocks::new();
+        chunk.set((4, 1, 4), Block::Stone, 0);
+        for y in 2..10 {
+            chunk.set((4, y, 4), Block::Sand, 0);
+        }
+        chunk.set((4, 1, 4), Block::Air, 0);
+        falling.notify((4, 1, 4));
+        settle(&mut chunk, &mut falling);
+
+        for y in 1..9 {
+            assert_eq!(*chunk.get((4, y, 4)).unwrap(), Block::Sand, "at y {}", y);
+        }
+        assert_eq!(*chunk.get((4, 9, 4)).unwrap(), Block::Air);
+        assert!(falling.entities.is_empty());
+    }
+
+    #[test]
+    fn block_lands_on_another() {
+        let mut chunk = floor();
+        let mut falling = FallingBlocks::new();
+        chunk.set((8, 1, 8), Block::Stone, 0);
+        chunk.set((8, 6, 8), Block::Gravel, 0);
+        falling.notify((8, 6, 8));
+        falling.update(&mut chunk, 1. / 60.);
+        assert_eq!(*chunk.get((8, 6, 8)).unwrap(), Block::Air);
+        assert_eq!(falling.entities.len(), 1);
+        settle(&mut chunk, &mut falling);
+
+        assert_eq!(*chunk.get((8, 1, 8)).unwrap(), Block::Stone);
+        assert_eq!(*chunk.get((8, 2, 8)).unwrap(), Block::Gravel);
+        assert!((3..HEIGHT).all(|y| *chunk.get((8, y, 8)).unwrap() == Block::Air));
+    }
+
+    #[test]
+    fn land_all_puts_falling_blocks_down_at_once() {
+        let mut chunk = floor();
+        let mut falling = FallingBlocks::new();
+        chunk.set((3, 9, 3), Block::Sand, 0);
+        falling.notify((3, 9, 3));
+        falling.update(&mut chunk, 1. / 60.);
+        assert_eq!(falling.entities.len(), 1);
+
+        assert_eq!(falling.land_all(&mut chunk), vec![(3, 1, 3)]);
+        assert!(falling.entities.is_empty());
+        assert_eq!(*chunk.get((3, 1, 3)).unwrap(), Block::Sand);
+        assert_eq!(*chunk.get((3, 9, 3)).unwrap(), Block::Air);
+    }
+
+    #[test]
+    fn blocks_landing_on_non_solid_blocks_are_blocked() {
+        for block in [Block::Ladder, Block::Water, Block::Portal] {
+            let mut chunk = floor();
+            let mut falling = FallingBlocks::new();
+            chunk.set((5, 1, 5), block, 0);
+            chunk.set((5, 7, 5), Block::Gravel, 0);
+            falling.notify((5, 7, 5));
+            settle(&mut chunk, &mut falling);
+
+            assert_eq!(*chunk.get((5, 1, 5)).unwrap(), block);
+            assert!((2..HEIGHT).all(|y| *chunk.get((5, y, 5)).unwrap() == Block::Air), "above {:?}", block);
+            assert_eq!(falling.blocked, vec![((5, 1, 5), Block::Gravel)]);
+        }
+    }
+
+    #[test]
+    fn supported_blocks_stay() {
+        let mut chunk = floor();
+        let mut falling = FallingBlocks::new();
+        chunk.set((2, 1, 2), Block::Sand, 0);
+        falling.notify((2, 1, 2));
+        assert!(falling.update(&mut chunk, 1. / 60.).is_empty());
+        assert_eq!(*chunk.get((2, 1, 2)).unwrap(), Block::Sand);
+    }
+}
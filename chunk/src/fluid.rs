@@ -0,0 +1,240 @@
+use crate::{Block, HeightChunk, CHUNK, HEIGHT};
+use crate::tick::{Scheduler, Update};
+
+// A liquid's level is how far it has flowed from a source. Sources never change on their
+// own; every other liquid block is recomputed from its neighbours and drains away once
+// nothing feeds it.
+pub const SOURCE: u8 = 0;
+// Flowing liquid with more of the same liquid directly above it
+pub const FALLING: u8 = 8;
+const MAX_FLOW: u8 = 7;
+// Ticks between a change and the liquid around it reacting, LAVA_RATE times longer near lava
+const FLOW_DELAY: u64 = 5;
+const LAVA_RATE: u64 = 3;
+
+type Pos = (usize, usize, usize);
+
+fn decay(liquid: Block) -> u8 {
+    if liquid == Block::Lava { 2 } else { 1 }
+}
+
+// Height of the liquid's surface as a fraction of the block
+pub fn surface(level: u8) -> f32 {
+    match level {
+        SOURCE | FALLING => 1.,
+        d => (8 - d.min(MAX_FLOW)) as f32 / 8.
+    }
+}
+
+fn horizontal(c: Pos) -> impl Iterator<Item = Pos> {
+    [(1, 0), (-1, 0), (0, 1), (0, -1)].into_iter().filter_map(move |(dx, dz): (i64, i64)| {
+        let x = c.0 as i64 + dx;
+        let z = c.2 as i64 + dz;
+        if x < 0 || z < 0 || x >= CHUNK as i64 || z >= CHUNK as i64 {
+            return None
+        }
+        return Some((x as usize, c.1, z as usize))
+    })
+}
+
+fn neighbours(c: Pos) -> impl Iterator<Item = Pos> {
+    let below = if c.1 > 0 { Some((c.0, c.1 - 1, c.2)) } else { None };
+    let above = if c.1 + 1 < HEIGHT { Some((c.0, c.1 + 1, c.2)) } else { None };
+    return horizontal(c).chain(below).chain(above)
+}
+
+fn block(chunk: &HeightChunk, c: Pos) -> Block {
+    *chunk.get(c).unwrap_or(&Block::Air)
+}
+
+// The bottom of the world holds liquid like a solid block
+fn solid_below(chunk: &HeightChunk, c: Pos) -> bool {
+    c.1 == 0 || block(chunk, (c.0, c.1 - 1, c.2)).is_solid()
+}
+
+// Call after changing a block so liquid around it can react. Lava nearby is slower.
+pub fn notify(ticks: &mut Scheduler, chunk: &HeightChunk, c: Pos) {
+    for n in std::iter::once(c).chain(neighbours(c)) {
+        let lava = std::iter::once(n).chain(neighbours(n)).any(|m| block(chunk, m) == Block::Lava);
+        ticks.schedule(n, Update::Flow, if lava { FLOW_DELAY * LAVA_RATE } else { FLOW_DELAY });
+    }
+}
+
+// Recomputes the liquid at cells, all from the same state of the chunk, then applies the
+// changes and schedules the blocks around them. Returns whether anything changed.
+pub fn step(chunk: &mut HeightChunk, cells: &[Pos], ticks: &mut Scheduler) -> bool {
+    let mut changes = Vec::new();
+    for c in cells {
+        let now = (block(chunk, *c), chunk.level(*c));
+        let after = next(chunk, *c);
+        if after != now {
+            changes.push((*c, after));
+        }
+    }
+    for (c, (b, level)) in changes.iter() {
+        chunk.set(*c, *b, *level);
+    }
+    for (c, _) in changes.iter() {
+        notify(ticks, chunk, *c);
+    }
+    return !changes.is_empty()
+}
+
+fn next(chunk: &HeightChunk, c: Pos) -> (Block, u8) {
+    let b = block(chunk, c);
+    let level = chunk.level(c);
+    if !(b == Block::Air || b.is_liquid()) {
+        return (b, level)
+    }
+
+    let next = if b.is_liquid() && level == SOURCE {
+        (b, level)
+    } else {
+        flow(chunk, c)
+    };
+
+    // Lava that touches water hardens
+    if next.0 == Block::Lava && neighbours(c).any(|n| block(chunk, n) == Block::Water) {
+        return (Block::Stone, 0)
+    }
+    return next
+}
+
+fn flow(chunk: &HeightChunk, c: Pos) -> (Block, u8) {
+    if c.1 + 1 < HEIGHT {
+        let above = block(chunk, (c.0, c.1 + 1, c.2));
+        if above.is_liquid() {
+            return (above, FALLING)
+        }
+    }
+
+    let mut best: Option<(Block, u8)> = None;
+    let mut sources = 0;
+    for n in horizontal(c) {
+        let nb = block(chunk, n);
+        let nl = chunk.level(n);
+        if !nb.is_liquid() {
+            continue
+        }
+        if nb == Block::Water && nl == SOURCE {
+            sources += 1;
+        }
+        // Liquid only spreads sideways out of sources or when it can't fall any further
+        if nl != SOURCE && !solid_below(chunk, n) {
+            continue
+        }
+        let d = if nl == SOURCE || nl == FALLING { 0 } else { nl } + decay(nb);
+        if d <= MAX_FLOW && best.is_none_or(|(_, bd)| d < bd) {
+            best = Some((nb, d));
+        }
+    }
+
+    // Water between two sources becomes a source itself, if something holds it up
+    let held = solid_below(chunk, c) || (block(chunk, (c.0, c.1 - 1, c.2)) == Block::Water && chunk.level((c.0, c.1 - 1, c.2)) == SOURCE);
+    if sources >= 2 && held {
+        return (Block::Water, SOURCE)
+    }
+    return best.unwrap_or((Block::Air, 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::falling::FallingBlocks;
+    use crate::tick;
+
+    // A stone floor at y = 0 with air above it
+    fn floor() -> HeightChunk {
+        HeightChunk::for_each(|_, y, _| if y == 0 { Block::Stone } else { Block::Air })
+    }
+
+    fn place(chunk: &mut HeightChunk, ticks: &mut Scheduler, c: Pos, b: Block) {
+        chunk.set(c, b, SOURCE);
+        notify(ticks, chunk, c);
+    }
+
+    // Runs ticks until no liquid is waiting to update, failing if that takes too long
+    fn settle(chunk: &mut HeightChunk, ticks: &mut Scheduler) {
+        let mut falling = FallingBlocks::new();
+        while !ticks.is_settled(Update::Flow) {
+            tick::run(chunk, ticks, &mut falling);
+            assert!(ticks.tick < 10000, "liquid never settled");
+        }
+    }
+
+    fn count(chunk: &HeightChunk, b: Block) -> usize {
+        (0..CHUNK).flat_map(|x| (0..HEIGHT).flat_map(move |y| (0..CHUNK).map(move |z| (x, y, z))))
+            .filter(|c| block(chunk, *c) == b)
+            .count()
+    }
+
+    #[test]
+    fn source_spreads_in_a_diamond() {
+        let mut chunk = floor();
+        let mut ticks = Scheduler::new(0);
+        place(&mut chunk, &mut ticks, (8, 1, 8), Block::Water);
+        settle(&mut chunk, &mut ticks);
+
+        // Levels rise by one per block from the source, out to MAX_FLOW
+        assert_eq!(count(&chunk, Block::Water), 113);
+        for x in 0..CHUNK {
+            for z in 0..CHUNK {
+                let d = (x as i64 - 8).unsigned_abs() + (z as i64 - 8).unsigned_abs();
+                let c = (x, 1, z);
+                if d <= MAX_FLOW as u64 {
+                    assert_eq!((block(&chunk, c), chunk.level(c)), (Block::Water, d as u8), "at {:?}", c);
+                } else {
+                    assert_eq!(block(&chunk, c), Block::Air, "at {:?}", c);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn flow_drains_without_its_source() {
+        let mut chunk = floor();
+        let mut ticks = Scheduler::new(0);
+        place(&mut chunk, &mut ticks, (8, 1, 8), Block::Water);
+        settle(&mut chunk, &mut ticks);
+        place(&mut chunk, &mut ticks, (8, 1, 8), Block::Air);
+        settle(&mut chunk, &mut ticks);
+        assert_eq!(count(&chunk, Block::Water), 0);
+    }
+
+    #[test]
+    fn lava_hardens_next_to_water() {
+        let mut chunk = floor();
+        let mut ticks = Scheduler::new(0);
+        place(&mut chunk, &mut ticks, (6, 1, 8), Block::Water);
+        place(&mut chunk, &mut ticks, (10, 1, 8), Block::Lava);
+        settle(&mut chunk, &mut ticks);
+
+        // Whichever reaches the other first hardens and walls the two off
+        assert!((7..10).any(|x| block(&chunk, (x, 1, 8)) == Block::Stone));
+        for x in 0..CHUNK {
+            for z in 0..CHUNK {
+                let c = (x, 1, z);
+                if block(&chunk, c) == Block::Lava {
+                    assert!(neighbours(c).all(|n| block(&chunk, n) != Block::Water), "lava next to water at {:?}", c);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn settles_and_stays_settled() {
+        let mut chunk = floor();
+        let mut ticks = Scheduler::new(0);
+        assert!(ticks.is_settled(Update::Flow));
+        place(&mut chunk, &mut ticks, (3, 1, 3), Block::Lava);
+        assert!(!ticks.is_settled(Update::Flow));
+        settle(&mut chunk, &mut ticks);
+        let before = chunk.to_bytes();
+        let mut falling = FallingBlocks::new();
+        for _ in 0..50 {
+            assert!(!tick::run(&mut chunk, &mut ticks, &mut falling));
+        }
+        assert!(ticks.is_settled(Update::Flow));
+        assert_eq!(chunk.to_bytes(), before);
+    }
+}
@@ -0,0 +1,404 @@
+use noise::{NoiseFn, Seedable, SuperSimplex};
+use crate::{Block, HeightChunk, CHUNK, HEIGHT};
+
+// Generators work in world coordinates so that neighbouring chunks line up
+pub trait Generator {
+    fn generate(&self, cx: i32, cz: i32) -> HeightChunk;
+}
+
+pub struct Flat {
+    pub layers: Vec<Block>
+}
+impl Generator for Flat {
+    fn generate(&self, _cx: i32, _cz: i32) -> HeightChunk {
+        return HeightChunk::for_each(|_, y, _| *self.layers.get(y).unwrap_or(&Block::Air))
+    }
+}
+
+// Stone from floor to ceiling, hollowed out by 3D noise, with lava pooling on the floor
+// and crystals scattered over the cave walls
+pub struct Caverns {
+    noise: SuperSimplex
+}
+impl Caverns {
+    pub fn new(seed: u32) -> Self {
+        Self { noise: SuperSimplex::new().set_seed(seed.wrapping_add(2)) }
+    }
+}
+impl Generator for Caverns {
+    fn generate(&self, cx: i32, cz: i32) -> HeightChunk {
+        let ox = cx as f64 * CHUNK as f64;
+        let oz = cz as f64 * CHUNK as f64;
+        return HeightChunk::for_each(|x, y, z| {
+            if y == 0 || y == HEIGHT - 1 {
+                return Block::Stone
+            }
+            let n = self.noise.get([(ox + x as f64) / 20., y as f64 / 6., (oz + z as f64) / 20.]);
+            if n > 0. {
+                return if y == 1 { Block::Lava } else { Block::Air }
+            }
+            let h = (ox as i64 + x as i64).wrapping_mul(73856093) ^ (y as i64).wrapping_mul(19349663) ^ (oz as i64 + z as i64).wrapping_mul(83492791);
+            if n > -0.05 && h.rem_euclid(40) == 0 { Block::Crystal } else { Block::Stone }
+        })
+    }
+}
+
+// Rivers start from springs in cells of this size and are followed a block at a time for
+// at most RIVER_STEPS blocks
+const RIVER_CELL: i64 = 64;
+const RIVER_STEPS: usize = 160;
+// Half the width of a river's water, and of the valley whose sides rise from it a block
+// per block
+const RIVER_WIDTH: f32 = 2.5;
+const VALLEY_WIDTH: f32 = 12.;
+// Mixed into the cell hash so springs are placed independently of lakes
+const RIVER_SALT: u64 = 0x5851F42D4C957F2D;
+const LAKE_CELL: i64 = 48;
+const LAKE_SAMPLES: usize = 16;
+const VINE_SALT: u64 = 0x2545F4914F6CDD1D;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Column {
+    pub ground: usize,
+    pub water: usize,
+    // Vines climb from the ground up to here, if it is above the ground
+    pub vines: usize
+}
+impl Column {
+    pub fn block(&self, y: usize) -> Block {
+        if y + 1 == self.ground {
+            if self.water >= self.ground { Block::Sand } else { Block::Grass }
+        } else if y + 4 > self.ground && y < self.ground {
+            Block::Dirt
+        } else if y < self.ground {
+            Block::Stone
+        } else if y < self.water {
+            Block::Water
+        } else if y < self.vines {
+            Block::Vine
+        } else {
+            Block::Air
+        }
+    }
+
+    // Water that would spill sideways onto a neighbour with lower water is held in by
+    // raising the neighbour's ground into a bank as high as it, so generated water is still
+    fn contain(self, neighbours: [Column; 4]) -> Column {
+        let mut ground = self.ground;
+        for n in neighbours {
+            if n.water > self.water && n.ground < n.water {
+                ground = ground.max(n.water);
+            }
+        }
+        return Column { ground, ..self }
+    }
+}
+
+// The middle of a river, a block between points, with the water level at each point
+struct River {
+    points: Vec<(f32, f32, f32)>
+}
+
+fn distance(x: i64, z: i64, px: f32, pz: f32) -> f32 {
+    return ((x as f32 - px).powi(2) + (z as f32 - pz).powi(2)).sqrt()
+}
+
+// Lowers ground and raises water for a column d away from a river point at level: a bed
+// under the water, then banks rising from the water's edge
+fn carve(ground: &mut f32, water: &mut f32, d: f32, level: f32) {
+    if d < RIVER_WIDTH {
+        *ground = ground.min(level - 1. - (1. - d * d / (RIVER_WIDTH * RIVER_WIDTH)) * 1.5);
+        *water = water.max(level);
+    } else if d < VALLEY_WIDTH {
+        *ground = ground.min(level + d - RIVER_WIDTH);
+    }
+}
+
+struct Lake {
+    x: f32,
+    z: f32,
+    radius: f32,
+    level: f32
+}
+
+pub struct Terrain {
+    pub seed: u32,
+    pub sea_level: f32,
+    height: SuperSimplex,
+    rivers: SuperSimplex
+}
+impl Terrain {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            seed,
+            sea_level: 4.,
+            height: SuperSimplex::new().set_seed(seed),
+            rivers: SuperSimplex::new().set_seed(seed.wrapping_add(1))
+        }
+    }
+
+    fn height_at(&self, x: f32, z: f32) -> f32 {
+        return self.height.get([x as f64 / 50., z as f64 / 50.]) as f32 * 10. + 5.
+    }
+
+    pub fn raw_height(&self, x: i64, z: i64) -> f32 {
+        return self.height_at(x as f32, z as f32)
+    }
+
+    fn cell_hash(&self, salt: u64, i: i64, j: i64) -> u64 {
+        let mut h = (self.seed as u64) ^ salt ^ (i as u64).wrapping_mul(0x9E3779B97F4A7C15) ^ (j as u64).wrapping_mul(0xC2B2AE3D27D4EB4F);
+        h ^= h >> 33;
+        h = h.wrapping_mul(0xFF51AFD7ED558CCD);
+        h ^= h >> 33;
+        return h
+    }
+
+    // About half the cells have a spring, if it is in the highlands. From there the river
+    // runs downhill, carried across dips by its own momentum and turned by noise so that it
+    // meanders. Its level is the lowest the land has been since the spring, so it only ever
+    // falls, and the river ends once it is out in the sea.
+    fn river(&self, i: i64, j: i64) -> Option<River> {
+        let h = self.cell_hash(RIVER_SALT, i, j);
+        if h & 1 == 1 {
+            return None
+        }
+        let mut x = (i * RIVER_CELL) as f32 + ((h >> 8) % RIVER_CELL as u64) as f32;
+        let mut z = (j * RIVER_CELL) as f32 + ((h >> 24) % RIVER_CELL as u64) as f32;
+        let mut level = self.height_at(x, z).floor() - 1.;
+        if level < self.sea_level + 3. {
+            return None
+        }
+        let (mut dz, mut dx) = (((h >> 40) % 360) as f32).to_radians().sin_cos();
+        let mut points = Vec::new();
+        for _ in 0..RIVER_STEPS {
+            let land = self.height_at(x, z);
+            level = level.min(land.floor() - 1.).max(self.sea_level);
+            points.push((x, z, level));
+            if land < self.sea_level {
+                break
+            }
+            let gx = self.height_at(x - 1., z) - self.height_at(x + 1., z);
+            let gz = self.height_at(x, z - 1.) - self.height_at(x, z + 1.);
+            let (s, c) = (self.rivers.get([x as f64 / 40., z as f64 / 40.]) as f32).sin_cos();
+            let (ax, az) = (gx * c - gz * s, gx * s + gz * c);
+            let downhill = (ax * ax + az * az).sqrt().max(f32::EPSILON);
+            dx = dx * 0.8 + ax / downhill * 0.2;
+            dz = dz * 0.8 + az / downhill * 0.2;
+            let len = (dx * dx + dz * dz).sqrt().max(f32::EPSILON);
+            dx /= len;
+            dz /= len;
+            x += dx;
+            z += dz;
+        }
+        return Some(River { points })
+    }
+
+    // Every river that comes within VALLEY_WIDTH of the area
+    fn rivers_near(&self, x0: i64, z0: i64, x1: i64, z1: i64) -> Vec<River> {
+        let reach = RIVER_STEPS as i64 + VALLEY_WIDTH as i64 + 1;
+        let mut rivers = Vec::new();
+        for i in (x0 - reach).div_euclid(RIVER_CELL)..=(x1 + reach).div_euclid(RIVER_CELL) {
+            for j in (z0 - reach).div_euclid(RIVER_CELL)..=(z1 + reach).div_euclid(RIVER_CELL) {
+                rivers.extend(self.river(i, j));
+            }
+        }
+        return rivers
+    }
+
+    // Ground and water heights from the land and the rivers
+    fn river_heights(&self, x: i64, z: i64, rivers: &[River]) -> (f32, f32) {
+        let (mut ground, mut water) = (self.raw_height(x, z), self.sea_level);
+        for &(px, pz, level) in rivers.iter().flat_map(|r| r.points.iter()) {
+            carve(&mut ground, &mut water, distance(x, z, px, pz), level);
+        }
+        return (ground, water)
+    }
+
+    // At most one lake per cell; its level is the lowest point of its rim, so the basin
+    // carved inside mostly holds the water. Where the rim dips between the samples,
+    // Column::contain banks it up.
+    fn lake(&self, i: i64, j: i64, rivers: &[River]) -> Option<Lake> {
+        let h = self.cell_hash(0, i, j);
+        if !h.is_multiple_of(3) {
+            return None
+        }
+        let margin = 16;
+        let span = (LAKE_CELL - margin * 2) as u64;
+        let x = (i * LAKE_CELL + margin) as f32 + ((h >> 8) % span) as f32;
+        let z = (j * LAKE_CELL + margin) as f32 + ((h >> 24) % span) as f32;
+        let radius = 6. + ((h >> 40) % 8) as f32;
+
+        let mut level = f32::MAX;
+        for s in 0..LAKE_SAMPLES {
+            let a = s as f32 / LAKE_SAMPLES as f32 * std::f32::consts::TAU;
+            let sx = (x + a.cos() * radius).round() as i64;
+            let sz = (z + a.sin() * radius).round() as i64;
+            level = level.min(self.river_heights(sx, sz, rivers).0);
+        }
+        let level = level.floor();
+        if level <= self.sea_level {
+            return None
+        }
+        return Some(Lake { x, z, radius, level })
+    }
+
+    // Lakes over the area, and the rivers their rims need to be measured against
+    fn lakes_near(&self, x0: i64, z0: i64, x1: i64, z1: i64) -> (Vec<Lake>, Vec<River>) {
+        let rivers = self.rivers_near(x0 - LAKE_CELL, z0 - LAKE_CELL, x1 + LAKE_CELL, z1 + LAKE_CELL);
+        let mut lakes = Vec::new();
+        for i in x0.div_euclid(LAKE_CELL)..=x1.div_euclid(LAKE_CELL) {
+            for j in z0.div_euclid(LAKE_CELL)..=z1.div_euclid(LAKE_CELL) {
+                lakes.extend(self.lake(i, j, &rivers));
+            }
+        }
+        return (lakes, rivers)
+    }
+
+    // The column from its heights after rivers, once lakes are carved into it
+    fn column_with(&self, x: i64, z: i64, (mut ground, mut water): (f32, f32), lakes: &[Lake]) -> Column {
+        for lake in lakes {
+            let d2 = ((x as f32 - lake.x).powi(2) + (z as f32 - lake.z).powi(2)) / lake.radius.powi(2);
+            if d2 < 1. {
+                ground = ground.min(lake.level - 1. - 3. * (1. - d2));
+                water = water.max(lake.level);
+            }
+        }
+
+        let ground = ground.ceil().clamp(0., HEIGHT as f32) as usize;
+        let water = water.ceil().clamp(0., HEIGHT as f32) as usize;
+        return Column { ground, water, vines: 0 }
+    }
+
+    // The same column generate gives, worked out on its own
+    pub fn column(&self, x: i64, z: i64) -> Column {
+        let (lakes, rivers) = self.lakes_near(x - 1, z - 1, x + 1, z + 1);
+        let at = |x, z| self.column_with(x, z, self.river_heights(x, z, &rivers), &lakes);
+        let neighbours = [at(x - 1, z), at(x + 1, z), at(x, z - 1), at(x, z + 1)];
+        return self.grow_vines(x, z, at(x, z).contain(neighbours), neighbours)
+    }
+
+    // Vines grow up the bank beside one in sixteen of the dry columns at the foot of one,
+    // reaching the top so they can be climbed out onto it
+    fn grow_vines(&self, x: i64, z: i64, column: Column, neighbours: [Column; 4]) -> Column {
+        let wall = neighbours.iter().map(|n| n.ground).max().unwrap();
+        let dry = column.water <= column.ground;
+        if dry && wall > column.ground && self.cell_hash(VINE_SALT, x, z) & 15 == 0 {
+            return Column { vines: wall, ..column }
+        }
+        return column
+    }
+}
+impl Generator for Terrain {
+    fn generate(&self, cx: i32, cz: i32) -> HeightChunk {
+        // Columns are contained against their neighbours, so one more is worked out all round
+        let size = CHUNK + 2;
+        let (ox, oz) = (cx as i64 * CHUNK as i64 - 1, cz as i64 * CHUNK as i64 - 1);
+        let (x1, z1) = (ox + size as i64 - 1, oz + size as i64 - 1);
+        let (lakes, rivers) = self.lakes_near(ox, oz, x1, z1);
+
+        let mut ground: Vec<f32> = (0..size * size).map(|i| self.raw_height(ox + (i / size) as i64, oz + (i % size) as i64)).collect();
+        let mut water = vec![self.sea_level; size * size];
+        // Each point of a river only reaches the columns around it
+        let reach = VALLEY_WIDTH.ceil() as i64;
+        for &(px, pz, level) in rivers.iter().flat_map(|r| r.points.iter()) {
+            let (rx, rz) = (px.round() as i64, pz.round() as i64);
+            for x in (rx - reach).max(ox)..=(rx + reach).min(x1) {
+                for z in (rz - reach).max(oz)..=(rz + reach).min(z1) {
+                    let i = (x - ox) as usize * size + (z - oz) as usize;
+                    carve(&mut ground[i], &mut water[i], distance(x, z, px, pz), level);
+                }
+            }
+        }
+        let cols: Vec<Column> = (0..size * size)
+            .map(|i| self.column_with(ox + (i / size) as i64, oz + (i % size) as i64, (ground[i], water[i]), &lakes))
+            .collect();
+        let at = |x: usize, z: usize| cols[x * size + z];
+        let contained: Vec<Column> = (0..CHUNK * CHUNK)
+            .map(|i| (i / CHUNK + 1, i % CHUNK + 1))
+            .map(|(x, z)| {
+                let neighbours = [at(x - 1, z), at(x + 1, z), at(x, z - 1), at(x, z + 1)];
+                self.grow_vines(ox + x as i64, oz + z as i64, at(x, z).contain(neighbours), neighbours)
+            })
+            .collect();
+        return HeightChunk::for_each(|x, y, z| contained[x * CHUNK + z].block(y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Chunks are generated from world coordinates, so the columns on either side of a
+    // chunk border are the ones Terrain::column gives for those coordinates
+    #[test]
+    fn chunks_line_up_across_borders() {
+        let terrain = Terrain::new(0);
+        let left = terrain.generate(0, 0);
+        let right = terrain.generate(1, 0);
+        for z in 0..CHUNK {
+            for (chunk, x, wx) in [(&left, CHUNK - 1, CHUNK as i64 - 1), (&right, 0, CHUNK as i64)] {
+                let column = terrain.column(wx, z as i64);
+                for y in 0..HEIGHT {
+                    assert_eq!(*chunk.get((x, y, z)).unwrap(), column.block(y), "column {} {} at y {}", wx, z, y);
+                }
+            }
+        }
+    }
+
+    // Generated water is still: every block of it rests on something and is held in at the
+    // sides, so it doesn't start flowing the first time a block near it changes
+    #[test]
+    fn generated_water_never_spills() {
+        let terrain = Terrain::new(0);
+        let chunks: Vec<Vec<HeightChunk>> = (0..2).map(|cx| (0..2).map(|cz| terrain.generate(cx, cz)).collect()).collect();
+        let block = |x: usize, y: usize, z: usize| *chunks[x / CHUNK][z / CHUNK].get((x % CHUNK, y, z % CHUNK)).unwrap();
+        let mut above_sea = 0;
+        let mut spills = Vec::new();
+        for x in 1..2 * CHUNK - 1 {
+            for z in 1..2 * CHUNK - 1 {
+                for y in 0..HEIGHT {
+                    if block(x, y, z) != Block::Water {
+                        continue
+                    }
+                    if y as f32 >= terrain.sea_level {
+                        above_sea += 1;
+                    }
+                    let below = if y > 0 { block(x, y - 1, z) } else { Block::Stone };
+                    if [below, block(x - 1, y, z), block(x + 1, y, z), block(x, y, z - 1), block(x, y, z + 1)].contains(&Block::Air) {
+                        spills.push((x, y, z));
+                    }
+                }
+            }
+        }
+        assert!(spills.is_empty(), "{} water blocks can spill, the first at {:?}", spills.len(), spills[0]);
+        // Rivers and lakes, not only the sea
+        assert!(above_sea > 100, "only {} water blocks above sea level", above_sea);
+    }
+
+    // Vines are something to climb: each one is on the ground or on another vine, and has
+    // a wall beside it
+    #[test]
+    fn vines_grow_up_banks() {
+        let terrain = Terrain::new(0);
+        let (mut vines, mut stranded) = (0, Vec::new());
+        for (cx, cz) in (0..4).flat_map(|cx| (0..4).map(move |cz| (cx, cz))) {
+            let chunk = terrain.generate(cx, cz);
+            let block = |x: usize, y: usize, z: usize| *chunk.get((x, y, z)).unwrap();
+            for (x, z) in (1..CHUNK - 1).flat_map(|x| (1..CHUNK - 1).map(move |z| (x, z))) {
+                for y in 1..HEIGHT {
+                    if block(x, y, z) != Block::Vine {
+                        continue
+                    }
+                    vines += 1;
+                    let below = block(x, y - 1, z);
+                    let walls = [block(x - 1, y, z), block(x + 1, y, z), block(x, y, z - 1), block(x, y, z + 1)];
+                    if !(below.is_solid() || below == Block::Vine) || !walls.iter().any(|b| b.is_solid()) {
+                        stranded.push((cx, cz, x, y, z));
+                    }
+                }
+            }
+        }
+        assert!(vines > 0, "no vines");
+        assert!(stranded.is_empty(), "{} vines have nothing to hold on to, the first at {:?}", stranded.len(), stranded[0]);
+    }
+}
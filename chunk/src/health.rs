@@ -0,0 +1,139 @@
+use crate::Block;
+
+pub const MAX_HEALTH: f32 = 20.;
+// Landing slower than this does no harm, which covers falls of up to three blocks. Every
+// block per second faster costs a point of health.
+pub const SAFE_IMPACT: f32 = 13.;
+pub const FALL_DAMAGE: f32 = 1.;
+// Hazard blocks hurt again after this many seconds of contact
+pub const HAZARD_INTERVAL: f32 = 0.5;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Damage {
+    Fall,
+    Block(Block),
+    // From a command, or anything else outside the world
+    Other
+}
+impl Damage {
+    pub fn describe(self) -> String {
+        match self {
+            Damage::Fall => "fell too far".to_string(),
+            Damage::Block(b) => format!("touched {}", b.name()),
+            Damage::Other => "died".to_string()
+        }
+    }
+}
+
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+    // What did the most recent damage, for death messages
+    pub last_damage: Option<Damage>,
+    // Seconds until a hazard block can hurt again
+    hazard_cooldown: f32
+}
+impl Default for Health {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Health {
+    pub fn new() -> Self {
+        Self { current: MAX_HEALTH, max: MAX_HEALTH, last_damage: None, hazard_cooldown: 0. }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.
+    }
+
+    // Returns whether this was the hit that killed
+    pub fn damage(&mut self, amount: f32, cause: Damage) -> bool {
+        if amount <= 0. || self.is_dead() {
+            return false
+        }
+        self.current = (self.current - amount).max(0.);
+        self.last_damage = Some(cause);
+        return self.is_dead()
+    }
+
+    pub fn heal(&mut self, amount: f32) {
+        if !self.is_dead() {
+            self.current = (self.current + amount).min(self.max);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.current = self.max;
+        self.last_damage = None;
+        self.hazard_cooldown = 0.;
+    }
+
+    // Damage from landing at impact blocks per second
+    pub fn land(&mut self, impact: f32) -> bool {
+        let amount = ((impact - SAFE_IMPACT) * FALL_DAMAGE).floor();
+        return self.damage(amount, Damage::Fall)
+    }
+
+    // Called every tick of dt seconds with the blocks the body is touching
+    pub fn touch(&mut self, blocks: impl Iterator<Item = Block>, dt: f32) -> bool {
+        self.hazard_cooldown = (self.hazard_cooldown - dt).max(0.);
+        let worst = blocks.max_by(|a, b| a.damage().total_cmp(&b.damage()));
+        match worst {
+            Some(b) if b.damage() > 0. && self.hazard_cooldown == 0. => {
+                self.hazard_cooldown = HAZARD_INTERVAL;
+                self.damage(b.damage(), Damage::Block(b))
+            },
+            _ => false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: f32 = 1. / 60.;
+
+    #[test]
+    fn falls_hurt_above_the_safe_impact() {
+        let mut health = Health::new();
+        assert!(!health.land(SAFE_IMPACT - 1.));
+        assert!(!health.land(SAFE_IMPACT));
+        assert_eq!(health.current, MAX_HEALTH);
+        assert_eq!(health.last_damage, None);
+
+        assert!(!health.land(SAFE_IMPACT + 2.5));
+        assert_eq!(health.current, MAX_HEALTH - 2.);
+        assert_eq!(health.last_damage, Some(Damage::Fall));
+        assert!(health.land(SAFE_IMPACT + MAX_HEALTH));
+        assert!(health.is_dead());
+    }
+
+    #[test]
+    fn hazard_blocks_hurt_once_per_interval() {
+        let mut health = Health::new();
+        assert!(!health.touch([Block::Stone, Block::Water].into_iter(), DT));
+        assert_eq!(health.current, MAX_HEALTH);
+
+        let lava = Block::Lava.damage();
+        health.touch([Block::Stone, Block::Lava].into_iter(), DT);
+        assert_eq!(health.current, MAX_HEALTH - lava);
+        assert_eq!(health.last_damage, Some(Damage::Block(Block::Lava)));
+
+        // Still in the lava, but it only bites again once the cooldown has run out
+        let ticks = (HAZARD_INTERVAL / DT).round() as usize;
+        for _ in 1..ticks {
+            health.touch([Block::Lava].into_iter(), DT);
+        }
+        assert_eq!(health.current, MAX_HEALTH - lava);
+        health.touch([Block::Lava].into_iter(), DT);
+        assert_eq!(health.current, MAX_HEALTH - 2. * lava);
+    }
+
+    #[test]
+    fn deaths_are_described_by_their_cause() {
+        assert_eq!(Damage::Fall.describe(), "fell too far");
+        assert_eq!(Damage::Block(Block::Lava).describe(), "touched lava");
+    }
+}
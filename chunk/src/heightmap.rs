@@ -0,0 +1,201 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use crate::gen::{Column, Generator};
+use crate::{Block, HeightChunk, CHUNK, HEIGHT};
+
+const SURFACE_BLOCKS: &[Block] = &[Block::Grass, Block::Dirt, Block::Sand, Block::Gravel, Block::Stone, Block::Water];
+
+struct Image {
+    width: usize,
+    height: usize,
+    channels: usize,
+    // Every sample scaled to 0..1, whatever the bit depth of the file
+    samples: Vec<f32>
+}
+impl Image {
+    fn load(path: &Path) -> io::Result<Image> {
+        let mut decoder = png::Decoder::new(File::open(path)?);
+        decoder.set_transformations(png::Transformations::EXPAND);
+        let mut reader = decoder.read_info()?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf)?;
+        buf.truncate(info.buffer_size());
+
+        let samples = match info.bit_depth {
+            png::BitDepth::Sixteen => buf.chunks_exact(2).map(|b| u16::from_be_bytes([b[0], b[1]]) as f32 / 65535.).collect(),
+            _ => buf.iter().map(|b| *b as f32 / 255.).collect()
+        };
+        return Ok(Image {
+            width: info.width as usize,
+            height: info.height as usize,
+            channels: info.color_type.samples(),
+            samples
+        })
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> &[f32] {
+        let i = (y * self.width + x) * self.channels;
+        return &self.samples[i..i + self.channels]
+    }
+
+    // Alpha is ignored, color is reduced to luminance
+    fn gray(&self, x: usize, y: usize) -> f32 {
+        let p = self.pixel(x, y);
+        if self.channels >= 3 {
+            return p[0] * 0.299 + p[1] * 0.587 + p[2] * 0.114
+        }
+        return p[0]
+    }
+
+    fn rgb(&self, x: usize, y: usize) -> [f32; 3] {
+        let p = self.pixel(x, y);
+        if self.channels >= 3 {
+            return [p[0], p[1], p[2]]
+        }
+        return [p[0], p[0], p[0]]
+    }
+}
+
+// Terrain read from a grayscale image: black is the bottom of the chunk, white the top.
+// Pixel (x, y) becomes the column at world (x, z).
+pub struct Heightmap {
+    width: usize,
+    depth: usize,
+    heights: Vec<usize>,
+    surface: Option<Vec<Block>>
+}
+impl Heightmap {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let img = Image::load(path)?;
+        let mut heights = Vec::with_capacity(img.width * img.height);
+        for y in 0..img.height {
+            for x in 0..img.width {
+                heights.push((img.gray(x, y) * HEIGHT as f32).round() as usize);
+            }
+        }
+        return Ok(Self { width: img.width, depth: img.height, heights, surface: None })
+    }
+
+    // Each pixel of the color map picks the surface block whose color is closest
+    pub fn with_colors(mut self, path: &Path) -> io::Result<Self> {
+        let img = Image::load(path)?;
+        if img.width != self.width || img.height != self.depth {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "color map size does not match heightmap"))
+        }
+        let mut surface = Vec::with_capacity(img.width * img.height);
+        for y in 0..img.height {
+            for x in 0..img.width {
+                let c = img.rgb(x, y);
+                let dist = |b: &Block| b.color().iter().zip(c.iter()).map(|(a, b)| (a - b).powi(2)).sum::<f32>();
+                surface.push(*SURFACE_BLOCKS.iter().min_by(|a, b| dist(a).total_cmp(&dist(b))).unwrap());
+            }
+        }
+        self.surface = Some(surface);
+        return Ok(self)
+    }
+
+    fn index(&self, x: i64, z: i64) -> Option<usize> {
+        if x < 0 || z < 0 || x as usize >= self.width || z as usize >= self.depth {
+            return None
+        }
+        return Some(z as usize * self.width + x as usize)
+    }
+}
+impl Generator for Heightmap {
+    fn generate(&self, cx: i32, cz: i32) -> HeightChunk {
+        let ox = cx as i64 * CHUNK as i64;
+        let oz = cz as i64 * CHUNK as i64;
+        return HeightChunk::for_each(|x, y, z| {
+            let i = match self.index(ox + x as i64, oz + z as i64) {
+                Some(i) => i,
+                None => return Block::Air
+            };
+            let ground = self.heights[i].min(HEIGHT);
+            match &self.surface {
+                Some(surface) if y + 1 == ground => surface[i],
+                _ => Column { ground, water: 0, vines: 0 }.block(y)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn write_png(name: &str, size: (u32, u32), color: png::ColorType, depth: png::BitDepth, data: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("chunk-heightmap-{}-{}.png", std::process::id(), name));
+        let mut encoder = png::Encoder::new(File::create(&path).unwrap(), size.0, size.1);
+        encoder.set_color(color);
+        encoder.set_depth(depth);
+        encoder.write_header().unwrap().write_image_data(data).unwrap();
+        return path
+    }
+
+    // Number of solid blocks in the column at x, z
+    fn ground(chunk: &HeightChunk, x: usize, z: usize) -> usize {
+        return chunk.top(x, z).map_or(0, |(y, _)| y + 1)
+    }
+
+    #[test]
+    fn gray_levels_map_onto_the_chunk_height() {
+        let path = write_png("8bit", (3, 2), png::ColorType::Grayscale, png::BitDepth::Eight, &[0, 128, 255, 64, 191, 32]);
+        let map = Heightmap::load(&path).unwrap();
+        fs::remove_file(&path).ok();
+        let chunk = map.generate(0, 0);
+        // Rows of the image run along z
+        let expected = [[0, 8, 16], [4, 12, 2]];
+        for (z, row) in expected.iter().enumerate() {
+            for (x, h) in row.iter().enumerate() {
+                assert_eq!(ground(&chunk, x, z), *h, "pixel {} {}", x, z);
+            }
+        }
+        assert_eq!(chunk.top(1, 0), Some((7, Block::Grass)));
+        assert_eq!(chunk.get((1, 3, 0)), Some(&Block::Stone));
+        // Past the edge of the image there is nothing
+        assert_eq!(chunk.top(3, 0), None);
+        assert_eq!(chunk.top(0, 2), None);
+    }
+
+    #[test]
+    fn sixteen_bit_samples_are_read_whole() {
+        let samples: [u16; 4] = [0, 4095, 32768, 61440];
+        let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_be_bytes()).collect();
+        let path = write_png("16bit", (4, 1), png::ColorType::Grayscale, png::BitDepth::Sixteen, &bytes);
+        let map = Heightmap::load(&path).unwrap();
+        fs::remove_file(&path).ok();
+        let chunk = map.generate(0, 0);
+        assert_eq!((0..4).map(|x| ground(&chunk, x, 0)).collect::<Vec<_>>(), vec![0, 1, 8, 15]);
+    }
+
+    #[test]
+    fn color_map_picks_the_closest_surface_block() {
+        let heights = write_png("heights", (2, 2), png::ColorType::Grayscale, png::BitDepth::Eight, &[128; 4]);
+        // Sand, stone, a slightly off water and a bright green
+        let colors = write_png("colors", (2, 2), png::ColorType::Rgb, png::BitDepth::Eight, &[
+            217, 204, 128, 128, 128, 128,
+            40, 90, 200, 60, 200, 40
+        ]);
+        let small = write_png("small", (1, 1), png::ColorType::Rgb, png::BitDepth::Eight, &[0, 0, 0]);
+        let map = Heightmap::load(&heights).unwrap().with_colors(&colors).unwrap();
+        let wrong_size = Heightmap::load(&heights).unwrap().with_colors(&small).err().map(|e| e.kind());
+        for path in [heights, colors, small] {
+            fs::remove_file(path).ok();
+        }
+        let chunk = map.generate(0, 0);
+        let tops: Vec<Block> = [(0, 0), (1, 0), (0, 1), (1, 1)].iter().map(|(x, z)| chunk.top(*x, *z).unwrap().1).collect();
+        assert_eq!(tops, vec![Block::Sand, Block::Stone, Block::Water, Block::Grass]);
+        // Only the top block takes the color, the rest of the column is as usual
+        assert_eq!(chunk.get((0, 6, 0)), Some(&Block::Dirt));
+        assert_eq!(wrong_size, Some(io::ErrorKind::InvalidData));
+    }
+
+    #[test]
+    fn missing_images_are_errors() {
+        let path = std::env::temp_dir().join("chunk-heightmap-does-not-exist.png");
+        assert_eq!(Heightmap::load(&path).err().map(|e| e.kind()), Some(io::ErrorKind::NotFound));
+    }
+}
@@ -0,0 +1,151 @@
+use crate::item::ItemStack;
+
+// The first HOTBAR slots are the hotbar, the rest are main storage
+pub const HOTBAR: usize = 9;
+pub const SLOTS: usize = 36;
+
+// What the player carries, as stacks of at most each item's max_stack in a fixed set of slots
+#[derive(Clone, Debug, PartialEq)]
+pub struct Inventory {
+    pub slots: [Option<ItemStack>; SLOTS],
+    // Hotbar slot whose item is in the player's hand
+    pub selected: usize
+}
+impl Default for Inventory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Inventory {
+    pub fn new() -> Self {
+        Self { slots: [None; SLOTS], selected: 0 }
+    }
+
+    // Tops up stacks of the same item first, then fills empty slots, hotbar before main
+    // storage. Returns what didn't fit.
+    pub fn add(&mut self, stack: ItemStack) -> Option<ItemStack> {
+        let max = stack.item.max_stack();
+        let mut left = stack.count;
+        for s in self.slots.iter_mut().flatten().filter(|s| s.item == stack.item) {
+            let moved = left.min(max.saturating_sub(s.count));
+            s.count += moved;
+            left -= moved;
+        }
+        for slot in self.slots.iter_mut().filter(|s| s.is_none()) {
+            if left == 0 {
+                break
+            }
+            let moved = left.min(max);
+            *slot = Some(ItemStack::new(stack.item, moved));
+            left -= moved;
+        }
+        return if left > 0 { Some(ItemStack::new(stack.item, left)) } else { None }
+    }
+
+    // Takes up to count items out of slot, emptying it when none are left
+    pub fn take(&mut self, slot: usize, count: u32) -> Option<ItemStack> {
+        let stack = self.slots.get_mut(slot)?.as_mut()?;
+        let taken = ItemStack::new(stack.item, count.min(stack.count));
+        stack.count -= taken.count;
+        if stack.count == 0 {
+            self.slots[slot] = None;
+        }
+        return if taken.count > 0 { Some(taken) } else { None }
+    }
+
+    pub fn held(&self) -> Option<ItemStack> {
+        self.slots[self.selected]
+    }
+
+    // Selects hotbar slot i, ignoring slots past the hotbar
+    pub fn select(&mut self, i: usize) {
+        if i < HOTBAR {
+            self.selected = i;
+        }
+    }
+
+    // Moves the selection by steps slots, wrapping around the ends of the hotbar
+    pub fn scroll(&mut self, steps: i32) {
+        self.selected = (self.selected as i32 + steps).rem_euclid(HOTBAR as i32) as usize;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Block;
+    use crate::breaking::Tool;
+    use crate::item::Item;
+
+    const STONE: Item = Item::Block(Block::Stone);
+    const DIRT: Item = Item::Block(Block::Dirt);
+
+    #[test]
+    fn tops_up_stacks_before_filling_slots() {
+        let mut inv = Inventory::new();
+        inv.slots[HOTBAR + 3] = Some(ItemStack::new(STONE, 60));
+        assert_eq!(inv.add(ItemStack::new(STONE, 10)), None);
+        assert_eq!(inv.slots[HOTBAR + 3], Some(ItemStack::new(STONE, 64)));
+        // The 6 that didn't fit go in the first empty slot, on the hotbar
+        assert_eq!(inv.slots[0], Some(ItemStack::new(STONE, 6)));
+        assert_eq!(inv.slots[1], None);
+    }
+
+    #[test]
+    fn splits_into_stacks_of_max_stack() {
+        let mut inv = Inventory::new();
+        assert_eq!(inv.add(ItemStack::new(DIRT, 150)), None);
+        assert_eq!(inv.slots[..3], [Some(ItemStack::new(DIRT, 64)), Some(ItemStack::new(DIRT, 64)), Some(ItemStack::new(DIRT, 22))]);
+        // Tools don't stack
+        let pick = Item::Tool(Tool::Pickaxe);
+        assert_eq!(inv.add(ItemStack::new(pick, 2)), None);
+        assert_eq!(inv.slots[3..5], [Some(ItemStack::new(pick, 1)), Some(ItemStack::new(pick, 1))]);
+    }
+
+    #[test]
+    fn fills_the_hotbar_then_main_storage() {
+        let mut inv = Inventory::new();
+        assert_eq!(inv.add(ItemStack::new(STONE, 64 * (HOTBAR as u32 + 1))), None);
+        assert!(inv.slots[..=HOTBAR].iter().all(|s| *s == Some(ItemStack::new(STONE, 64))));
+        assert!(inv.slots[HOTBAR + 1..].iter().all(|s| s.is_none()));
+    }
+
+    #[test]
+    fn returns_what_does_not_fit() {
+        let mut inv = Inventory::new();
+        assert_eq!(inv.add(ItemStack::new(STONE, 64 * SLOTS as u32 - 1)), None);
+        assert_eq!(inv.add(ItemStack::new(STONE, 5)), Some(ItemStack::new(STONE, 4)));
+        assert_eq!(inv.add(ItemStack::new(DIRT, 1)), Some(ItemStack::new(DIRT, 1)));
+    }
+
+    #[test]
+    fn takes_from_a_slot() {
+        let mut inv = Inventory::new();
+        inv.slots[2] = Some(ItemStack::new(DIRT, 5));
+        assert_eq!(inv.take(2, 3), Some(ItemStack::new(DIRT, 3)));
+        assert_eq!(inv.slots[2], Some(ItemStack::new(DIRT, 2)));
+        assert_eq!(inv.take(2, 10), Some(ItemStack::new(DIRT, 2)));
+        assert_eq!(inv.slots[2], None);
+        assert_eq!(inv.take(2, 1), None);
+        assert_eq!(inv.take(SLOTS, 1), None);
+    }
+
+    #[test]
+    fn selects_and_scrolls_the_hotbar() {
+        let mut inv = Inventory::new();
+        inv.select(4);
+        assert_eq!(inv.selected, 4);
+        inv.select(HOTBAR);
+        assert_eq!(inv.selected, 4);
+        inv.slots[4] = Some(ItemStack::new(STONE, 1));
+        assert_eq!(inv.held(), Some(ItemStack::new(STONE, 1)));
+
+        inv.select(0);
+        inv.scroll(-1);
+        assert_eq!(inv.selected, HOTBAR - 1);
+        inv.scroll(1);
+        assert_eq!(inv.selected, 0);
+        inv.scroll(HOTBAR as i32 * 2 + 3);
+        assert_eq!(inv.selected, 3);
+    }
+}
@@ -0,0 +1,65 @@
+use crate::Block;
+use crate::breaking::Tool;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Item {
+    Block(Block),
+    Tool(Tool)
+}
+impl Item {
+    // Blocks and tools share one namespace, so no tool may be named after a block
+    pub fn name(self) -> &'static str {
+        match self {
+            Item::Block(b) => b.name(),
+            Item::Tool(t) => t.name()
+        }
+    }
+
+    // Only blocks that can be picked up are items, so no stacks of air or water
+    pub fn from_name(name: &str) -> Option<Item> {
+        let block = Block::from_name(name).filter(|b| b.drop().is_some());
+        block.map(Item::Block).or_else(|| Tool::from_name(name).map(Item::Tool))
+    }
+
+    // Tools don't stack
+    pub fn max_stack(self) -> u32 {
+        match self {
+            Item::Block(_) => 64,
+            Item::Tool(_) => 1
+        }
+    }
+
+    pub fn color(self) -> [f32; 3] {
+        match self {
+            Item::Block(b) => b.color(),
+            Item::Tool(Tool::Hand) => [0.9, 0.75, 0.6],
+            Item::Tool(Tool::Pickaxe) => [0.7, 0.7, 0.75],
+            Item::Tool(Tool::Shovel) => [0.6, 0.5, 0.35]
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ItemStack {
+    pub item: Item,
+    pub count: u32
+}
+impl ItemStack {
+    pub fn new(item: Item, count: u32) -> Self {
+        Self { item, count }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_things_that_can_be_carried_are_items() {
+        assert_eq!(Item::from_name("stone"), Some(Item::Block(Block::Stone)));
+        assert_eq!(Item::from_name("pickaxe"), Some(Item::Tool(Tool::Pickaxe)));
+        for name in ["air", "water", "lava", "portal", "nothing"] {
+            assert_eq!(Item::from_name(name), None, "{}", name);
+        }
+    }
+}
@@ -1,22 +1,53 @@
-const CHUNK: usize = 128;
-const HEIGHT: usize = 16;
-type HV = bool;
+pub const CHUNK: usize = 128;
+pub const HEIGHT: usize = 16;
+type HV = Block;
 
 use cgmath::{Point3, Vector3};
 use cgmath::InnerSpace;
 
+mod block;
+pub use block::Block;
+
+pub mod gen;
+pub mod heightmap;
+pub mod save;
+pub mod map;
+pub mod hash;
+pub mod fluid;
+pub mod falling;
+pub mod tick;
+pub mod light;
+pub mod mesh;
+pub mod physics;
+pub mod health;
+pub mod spawn;
+pub mod player;
+pub mod breaking;
+pub mod item;
+pub mod inventory;
+pub mod entity;
+pub mod crafting;
+
+// Boxed, as a chunk's worth of blocks is too much to move around on the stack
+type Grid<T> = Box<[[[T; CHUNK]; HEIGHT]; CHUNK]>;
+
+fn grid<T: Copy>(value: T) -> Grid<T> {
+    return vec![[[value; CHUNK]; HEIGHT]; CHUNK].into_boxed_slice().try_into().unwrap_or_else(|_| unreachable!())
+}
+
 pub struct HeightChunk {
-    data: [[[HV; CHUNK]; HEIGHT]; CHUNK]
+    data: Grid<HV>,
+    // Extra state for each block, for now only the flow level of liquids (see fluid)
+    levels: Grid<u8>,
+    light: Grid<u16>
 }
 impl HeightChunk {
     pub fn value(value: HV) -> Self {
-        Self {
-            data: [[[value; CHUNK]; HEIGHT]; CHUNK]
-        }
+        return Self::for_each(|_, _, _| value)
     }
 
     pub fn for_each<T: Fn(usize, usize, usize) -> HV>(f: T) -> Self {
-        let mut data = [[[false; CHUNK]; HEIGHT]; CHUNK];
+        let mut data = grid(Block::Air);
         for (x,row) in data.iter_mut().enumerate() {
             for (y,col) in row.iter_mut().enumerate() {
                 for (z,cell) in col.iter_mut().enumerate() {
@@ -24,7 +55,13 @@ impl HeightChunk {
                 }
             }
         };
-        return HeightChunk { data }
+        let mut chunk = HeightChunk {
+            data,
+            levels: grid(0),
+            light: grid(0)
+        };
+        chunk.compute_light();
+        return chunk
     }
 
     pub fn get(&self, c: (usize, usize, usize)) -> Option<&HV> {
@@ -34,6 +71,20 @@ impl HeightChunk {
         return self.data.get_mut(c.0).and_then(|row| row.get_mut(c.1)).and_then(|row| row.get_mut(c.2))
     }
 
+    pub fn level(&self, c: (usize, usize, usize)) -> u8 {
+        return *self.levels.get(c.0).and_then(|row| row.get(c.1)).and_then(|row| row.get(c.2)).unwrap_or(&0)
+    }
+    pub fn set(&mut self, c: (usize, usize, usize), block: HV, level: u8) {
+        if let Some(cell) = self.get_mut(c) {
+            let old = *cell;
+            *cell = block;
+            self.levels[c.0][c.1][c.2] = level;
+            if old.is_solid() != block.is_solid() || old.emission() != block.emission() {
+                self.update_light(c);
+            }
+        }
+    }
+
     pub fn to_index(p: cgmath::Point3<f32>) -> (usize, usize, usize) {
         (p.x.round() as usize,p.y.round() as usize,p.z.round() as usize)
     }
@@ -42,37 +93,76 @@ impl HeightChunk {
         return self.get(Self::to_index(p));
     }
     pub fn is_empty(&self, p: cgmath::Point3<f32>) -> bool {
-        ! *self.getp(p).unwrap_or(&false)
+        ! self.getp(p).is_some_and(|b| b.is_solid())
     }
     pub fn is_empty_raw(&self, c: (usize,usize,usize)) -> bool {
-        ! *self.get(c).unwrap_or(&false)
+        ! self.get(c).is_some_and(|b| b.is_solid())
+    }
+
+    // Highest non-air block in a column
+    pub fn top(&self, x: usize, z: usize) -> Option<(usize, HV)> {
+        return (0..HEIGHT).rev().filter_map(|y| Some((y, *self.get((x, y, z))?))).find(|(_, b)| *b != Block::Air)
     }
 
     pub fn ray(&self, start: Point3<f32>, ray: Vector3<f32>) -> Option<((usize,usize,usize),HV)> {
+        let (hit, _) = self.cast(start, ray)?;
+        return Some((Self::to_index(hit), *self.getp(hit).unwrap()))
+    }
+
+    // The air block the ray passed through just before it hit something, where a block
+    // placed against what it hit goes
+    pub fn ray_before(&self, start: Point3<f32>, ray: Vector3<f32>) -> Option<(usize,usize,usize)> {
+        let (_, before) = self.cast(start, ray)?;
+        return before.filter(|p| self.getp(*p) == Some(&Block::Air)).map(Self::to_index)
+    }
+
+    // Where the ray first hits a targetable block, and the point sampled before that
+    fn cast(&self, start: Point3<f32>, ray: Vector3<f32>) -> Option<(Point3<f32>, Option<Point3<f32>>)> {
         let mut dist: f32 = 0.;
+        let mut before = None;
         loop {
             let point = start + ray.normalize() * dist;
-            if *self.getp(point).unwrap_or(&false) {
-                return Some((Self::to_index(point), *self.getp(point).unwrap()))
+            if self.getp(point).is_some_and(|b| b.targetable()) {
+                return Some((point, before))
             }
             
             if dist < ray.magnitude() {
+                before = Some(point);
                 dist += 1.;
             } else {
                 return None
             }
         }
     }
+}
 
-    pub fn positions(&self) -> Vec<[f32; 3]> {
-        let mut pos = Vec::<[f32;3]>::new();
-        for (x, row) in self.data.iter().enumerate() {
-            for (y, col) in row.iter().enumerate() {
-                for (z, cell) in col.iter().enumerate() {
-                    if *cell && self.is_empty_raw((x,y + 1,z)) { pos.push([x as f32,y as f32,z as f32]) }
-                }
-            }
-        }
-        return pos
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rays_stop_at_climbable_blocks_but_not_liquids() {
+        let mut chunk = HeightChunk::for_each(|x, _, _| if x == 12 { Block::Stone } else { Block::Air });
+        let eye = Point3::new(2., 5., 8.);
+        let ray = Vector3::new(10., 0., 0.);
+        assert_eq!(chunk.ray(eye, ray), Some(((12, 5, 8), Block::Stone)));
+
+        chunk.set((6, 5, 8), Block::Water, 0);
+        assert_eq!(chunk.ray(eye, ray), Some(((12, 5, 8), Block::Stone)));
+        chunk.set((8, 5, 8), Block::Vine, 0);
+        assert_eq!(chunk.ray(eye, ray), Some(((8, 5, 8), Block::Vine)));
+        chunk.set((4, 5, 8), Block::Ladder, 0);
+        assert_eq!(chunk.ray(eye, ray), Some(((4, 5, 8), Block::Ladder)));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn blocks_are_placed_in_the_air_in_front_of_the_target() {
+        let mut chunk = HeightChunk::for_each(|x, _, _| if x == 12 { Block::Stone } else { Block::Air });
+        let eye = Point3::new(2., 5., 8.);
+        assert_eq!(chunk.ray_before(eye, Vector3::new(10., 0., 0.)), Some((11, 5, 8)));
+        assert_eq!(chunk.ray_before(eye, Vector3::new(5., 0., 0.)), None);
+
+        chunk.set((11, 5, 8), Block::Water, 0);
+        assert_eq!(chunk.ray_before(eye, Vector3::new(10., 0., 0.)), None);
+    }
+}
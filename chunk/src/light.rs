@@ -0,0 +1,248 @@
+use std::collections::VecDeque;
+use crate::{Block, HeightChunk, CHUNK, HEIGHT};
+
+// Light is stored per block as four levels from 0 to MAX_LIGHT, a nibble each: sky light,
+// then the red, green and blue block light given off by emissive blocks. Light loses a level
+// per block it travels, except full sky light, which shines straight down without fading.
+pub const MAX_LIGHT: u8 = 15;
+
+type Pos = (usize, usize, usize);
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Channel {
+    Sky,
+    Red,
+    Green,
+    Blue
+}
+const CHANNELS: [Channel; 4] = [Channel::Sky, Channel::Red, Channel::Green, Channel::Blue];
+
+impl Channel {
+    fn shift(self) -> u16 {
+        match self {
+            Channel::Sky => 12,
+            Channel::Red => 8,
+            Channel::Green => 4,
+            Channel::Blue => 0
+        }
+    }
+}
+
+// Neighbours, and whether each one is directly below
+fn neighbours(c: Pos) -> impl Iterator<Item = (Pos, bool)> {
+    [(1, 0, 0), (-1, 0, 0), (0, 1, 0), (0, -1, 0), (0, 0, 1), (0, 0, -1)].into_iter().filter_map(move |(dx, dy, dz): (i64, i64, i64)| {
+        let n = (c.0 as i64 + dx, c.1 as i64 + dy, c.2 as i64 + dz);
+        if n.0 < 0 || n.1 < 0 || n.2 < 0 || n.0 >= CHUNK as i64 || n.1 >= HEIGHT as i64 || n.2 >= CHUNK as i64 {
+            return None
+        }
+        return Some(((n.0 as usize, n.1 as usize, n.2 as usize), dy < 0))
+    })
+}
+
+impl HeightChunk {
+    pub fn sky_light(&self, c: Pos) -> u8 {
+        self.light_of(Channel::Sky, c)
+    }
+    pub fn block_light(&self, c: Pos) -> [u8; 3] {
+        [self.light_of(Channel::Red, c), self.light_of(Channel::Green, c), self.light_of(Channel::Blue, c)]
+    }
+
+    fn light_of(&self, ch: Channel, c: Pos) -> u8 {
+        match self.light.get(c.0).and_then(|row| row.get(c.1)).and_then(|row| row.get(c.2)) {
+            Some(l) => ((l >> ch.shift()) & 0xF) as u8,
+            // Everything outside the chunk is open sky
+            None => if ch == Channel::Sky { MAX_LIGHT } else { 0 }
+        }
+    }
+    fn set_light(&mut self, ch: Channel, c: Pos, level: u8) {
+        let l = &mut self.light[c.0][c.1][c.2];
+        *l = (*l & !(0xF << ch.shift())) | ((level as u16) << ch.shift());
+    }
+
+    fn opaque(&self, c: Pos) -> bool {
+        self.get(c).is_some_and(|b| b.is_solid())
+    }
+
+    // Light a block gives off by itself: its emission, or sky light for the top layer.
+    // Emissive blocks shine even when they are solid.
+    fn source(&self, ch: Channel, c: Pos) -> u8 {
+        let b = *self.get(c).unwrap_or(&Block::Air);
+        match ch {
+            Channel::Sky => if c.1 == HEIGHT - 1 && !b.is_solid() { MAX_LIGHT } else { 0 },
+            Channel::Red => b.emission()[0],
+            Channel::Green => b.emission()[1],
+            Channel::Blue => b.emission()[2]
+        }
+    }
+
+    fn spread(&mut self, ch: Channel, mut queue: VecDeque<Pos>) {
+        while let Some(p) = queue.pop_front() {
+            let lv = self.light_of(ch, p);
+            for (n, down) in neighbours(p) {
+                if self.opaque(n) {
+                    continue
+                }
+                let nv = if ch == Channel::Sky && down && lv == MAX_LIGHT { MAX_LIGHT } else { lv.saturating_sub(1) };
+                if nv > self.light_of(ch, n) {
+                    self.set_light(ch, n, nv);
+                    queue.push_back(n);
+                }
+            }
+        }
+    }
+
+    // Darkens everything that was lit through c, which had the level old. Returns the lit
+    // blocks bordering the darkened area, which need to spread their light back in.
+    fn unspread(&mut self, ch: Channel, c: Pos, old: u8) -> VecDeque<Pos> {
+        let mut queue = VecDeque::from([(c, old)]);
+        let mut relight = VecDeque::new();
+        while let Some((p, lv)) = queue.pop_front() {
+            for (n, down) in neighbours(p) {
+                let nl = self.light_of(ch, n);
+                if nl == 0 {
+                    continue
+                }
+                if nl < lv || (ch == Channel::Sky && down && lv == MAX_LIGHT && nl == MAX_LIGHT) {
+                    self.set_light(ch, n, 0);
+                    queue.push_back((n, nl));
+                    let own = self.source(ch, n);
+                    if own > 0 {
+                        self.set_light(ch, n, own);
+                        relight.push_back(n);
+                    }
+                } else {
+                    relight.push_back(n);
+                }
+            }
+        }
+        return relight
+    }
+
+    // Relights the whole chunk from scratch
+    pub fn compute_light(&mut self) {
+        self.light = crate::grid(0);
+        for ch in CHANNELS {
+            let mut queue = VecDeque::new();
+            for x in 0..CHUNK {
+                for y in 0..HEIGHT {
+                    for z in 0..CHUNK {
+                        let own = self.source(ch, (x, y, z));
+                        if own > 0 {
+                            self.set_light(ch, (x, y, z), own);
+                            queue.push_back((x, y, z));
+                        }
+                    }
+                }
+            }
+            self.spread(ch, queue);
+        }
+    }
+
+    // Fixes up light around c after the block there changed
+    pub fn update_light(&mut self, c: Pos) {
+        for ch in CHANNELS {
+            let old = self.light_of(ch, c);
+            self.set_light(ch, c, 0);
+            let mut relight = self.unspread(ch, c, old);
+            let own = self.source(ch, c);
+            self.set_light(ch, c, own);
+            if own > 0 {
+                relight.push_back(c);
+            }
+            if !self.opaque(c) {
+                relight.extend(neighbours(c).map(|(n, _)| n));
+            }
+            self.spread(ch, relight);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A closed room, so block light isn't drowned out by the sky
+    fn cave() -> HeightChunk {
+        HeightChunk::for_each(|x, y, z| {
+            let inside = (2..CHUNK - 2).contains(&x) && (3..8).contains(&y) && (2..CHUNK - 2).contains(&z);
+            if inside || y >= 10 { Block::Air } else { Block::Stone }
+        })
+    }
+
+    // Light after incremental updates has to be the same as relighting the chunk from scratch
+    fn assert_relit(chunk: &HeightChunk) {
+        let full = HeightChunk::for_each(|x, y, z| *chunk.get((x, y, z)).unwrap());
+        for x in 0..CHUNK {
+            for y in 0..HEIGHT {
+                for z in 0..CHUNK {
+                    assert_eq!(chunk.light[x][y][z], full.light[x][y][z], "at {:?}", (x, y, z));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn placing_and_removing_an_emitter_matches_a_full_relight() {
+        let mut chunk = cave();
+        chunk.set((7, 4, 7), Block::Crystal, 0);
+        assert_relit(&chunk);
+        assert_eq!(chunk.block_light((7, 4, 7)), [3, 8, 15]);
+        assert_eq!(chunk.block_light((7, 4, 10)), [0, 5, 12]);
+
+        chunk.set((4, 3, 4), Block::Lava, 0);
+        assert_relit(&chunk);
+
+        chunk.set((7, 4, 7), Block::Air, 0);
+        assert_relit(&chunk);
+        chunk.set((4, 3, 4), Block::Air, 0);
+        assert_relit(&chunk);
+        assert_eq!(chunk.block_light((7, 4, 7)), [0, 0, 0]);
+    }
+
+    #[test]
+    fn placing_and_removing_an_opaque_block_matches_a_full_relight() {
+        let mut chunk = cave();
+        chunk.set((3, 4, 7), Block::Crystal, 0);
+        // A wall across the room, with a gap at the top to light around
+        for y in 3..7 {
+            for z in 2..CHUNK - 2 {
+                chunk.set((6, y, z), Block::Stone, 0);
+            }
+            assert_relit(&chunk);
+        }
+        // Up over the wall and down again
+        assert_eq!(chunk.block_light((8, 4, 7))[2], 15 - 11);
+
+        chunk.set((6, 4, 7), Block::Air, 0);
+        assert_relit(&chunk);
+        assert_eq!(chunk.block_light((8, 4, 7))[2], 15 - 5);
+        chunk.set((6, 4, 7), Block::Stone, 0);
+        assert_relit(&chunk);
+    }
+
+    #[test]
+    fn sky_light_fades_under_an_overhang_and_returns_without_it() {
+        let mut chunk = HeightChunk::for_each(|_, y, _| if y < 3 { Block::Stone } else { Block::Air });
+        assert_eq!(chunk.sky_light((2, 3, 7)), MAX_LIGHT);
+
+        // A roof over the low x half of the chunk, open along the x = 8 edge
+        for x in 0..8 {
+            for z in 0..CHUNK {
+                chunk.set((x, 6, z), Block::Stone, 0);
+            }
+        }
+        assert_relit(&chunk);
+        assert_eq!(chunk.sky_light((10, 3, 7)), MAX_LIGHT);
+        assert_eq!(chunk.sky_light((7, 3, 7)), MAX_LIGHT - 1);
+        assert_eq!(chunk.sky_light((2, 3, 7)), MAX_LIGHT - 6);
+        assert_eq!(chunk.sky_light((2, 6, 7)), 0);
+
+        for x in 0..8 {
+            for z in 0..CHUNK {
+                chunk.set((x, 6, z), Block::Air, 0);
+            }
+        }
+        assert_relit(&chunk);
+        assert!((0..CHUNK).all(|x| chunk.sky_light((x, 3, 7)) == MAX_LIGHT));
+    }
+}
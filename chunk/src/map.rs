@@ -0,0 +1,90 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+use crate::{HeightChunk, CHUNK, HEIGHT};
+
+// Top-down RGB image with one pixel per column: x runs along world x, y along world z
+pub struct MapImage {
+    pub width: usize,
+    pub height: usize,
+    pub rgb: Vec<u8>
+}
+impl MapImage {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self { width, height, rgb: vec![0; width * height * 3] }
+    }
+
+    // Columns are colored by their top block and darken toward the bottom of the chunk
+    pub fn column_color(chunk: &HeightChunk, x: usize, z: usize) -> [u8; 3] {
+        match chunk.top(x, z) {
+            Some((y, block)) => {
+                let shade = 0.4 + 0.6 * (y + 1) as f32 / HEIGHT as f32;
+                block.color().map(|c| (c * shade * 255.).round() as u8)
+            },
+            None => [0, 0, 0]
+        }
+    }
+
+    pub fn draw_chunk(&mut self, px: usize, py: usize, chunk: &HeightChunk) {
+        for x in 0..CHUNK.min(self.width.saturating_sub(px)) {
+            for z in 0..CHUNK.min(self.height.saturating_sub(py)) {
+                let i = ((py + z) * self.width + px + x) * 3;
+                self.rgb[i..i + 3].copy_from_slice(&Self::column_color(chunk, x, z));
+            }
+        }
+    }
+
+    pub fn write_png(&self, path: &Path) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.rgb)?;
+        return Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Block;
+
+    // Stone rising a block per column along x, up to the top of the chunk
+    fn slope() -> HeightChunk {
+        return HeightChunk::for_each(|x, y, _| if y <= x { Block::Stone } else { Block::Air })
+    }
+
+    #[test]
+    fn columns_are_colored_by_top_block_and_shaded_by_height() {
+        let chunk = slope();
+        assert_eq!(MapImage::column_color(&chunk, HEIGHT - 1, 0), [128, 128, 128]);
+        assert_eq!(MapImage::column_color(&chunk, 0, 0), [56, 56, 56]);
+        assert_eq!(MapImage::column_color(&HeightChunk::value(Block::Air), 0, 0), [0, 0, 0]);
+    }
+
+    #[test]
+    fn chunks_are_drawn_at_their_offset_and_clipped_to_the_image() {
+        let chunk = slope();
+        let mut image = MapImage::new(CHUNK + 10, 20);
+        image.draw_chunk(10, 5, &chunk);
+        let pixel = |x: usize, y: usize| &image.rgb[(y * image.width + x) * 3..][..3];
+        assert_eq!(pixel(10, 5), MapImage::column_color(&chunk, 0, 0));
+        assert_eq!(pixel(CHUNK + 9, 19), MapImage::column_color(&chunk, CHUNK - 1, 14));
+        assert_eq!(pixel(9, 5), [0, 0, 0]);
+        assert_eq!(pixel(10, 4), [0, 0, 0]);
+    }
+
+    #[test]
+    fn writes_the_image_as_png() {
+        let mut image = MapImage::new(CHUNK, 3);
+        image.draw_chunk(0, 0, &slope());
+        let path = std::env::temp_dir().join(format!("chunk-map-{}.png", std::process::id()));
+        image.write_png(&path).unwrap();
+        let mut reader = png::Decoder::new(File::open(&path).unwrap()).read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!((info.width, info.height, info.color_type), (CHUNK as u32, 3, png::ColorType::Rgb));
+        assert_eq!(&buf[..info.buffer_size()], &image.rgb[..]);
+    }
+}
@@ -0,0 +1,165 @@
+use crate::{fluid, Block, HeightChunk};
+use crate::light::MAX_LIGHT;
+
+// The world is drawn as one instanced quad per visible block face. Each instance is
+// position (3), face (1), color (3), height (1), sky light (1), block light (3), ambient
+// occlusion at the four corners (4), size (1), which shrinks the cube around its centre,
+// and 1 if the quad is split along its other diagonal (1), see flips.
+pub const FACE_SIZE: usize = 18;
+pub type FaceInstance = [f32; FACE_SIZE];
+
+type Dir = (i64, i64, i64);
+
+// Outward direction of each face, in the order the shader numbers them
+pub const FACES: [Dir; 6] = [(1, 0, 0), (-1, 0, 0), (0, 1, 0), (0, -1, 0), (0, 0, 1), (0, 0, -1)];
+// Directions the shader's u and v corner coordinates run along on each face
+const FACE_AXES: [(Dir, Dir); 6] = [
+    ((0, 1, 0), (0, 0, 1)), ((0, 1, 0), (0, 0, 1)),
+    ((1, 0, 0), (0, 0, 1)), ((1, 0, 0), (0, 0, 1)),
+    ((1, 0, 0), (0, 1, 0)), ((1, 0, 0), (0, 1, 0))
+];
+// Quad corners as (u, v), in the order of the vertices the shader draws
+const CORNERS: [(i64, i64); 4] = [(0, 0), (1, 0), (1, 1), (0, 1)];
+// Unoccluded, for faces drawn away from the chunk
+pub const NO_OCCLUSION: [f32; 4] = [1.; 4];
+
+pub fn face(pos: [f32; 3], face: usize, block: Block, height: f32, light: (u8, [u8; 3]), ao: [f32; 4]) -> FaceInstance {
+    return colored_face(pos, face, block.color(), height, light, ao, 1.)
+}
+
+pub fn colored_face(pos: [f32; 3], face: usize, c: [f32; 3], height: f32, light: (u8, [u8; 3]), ao: [f32; 4], size: f32) -> FaceInstance {
+    let l = |v: u8| v as f32 / MAX_LIGHT as f32;
+    return [
+        pos[0], pos[1], pos[2], face as f32, c[0], c[1], c[2], height,
+        l(light.0), l(light.1[0]), l(light.1[1]), l(light.1[2]),
+        ao[0], ao[1], ao[2], ao[3], size, flips(ao) as u8 as f32
+    ]
+}
+
+// Quads are split into triangles along their 0-2 diagonal. When the other diagonal is
+// brighter they are split along that one instead, so the occlusion gradient doesn't smear
+// along the wrong diagonal.
+pub fn flips(ao: [f32; 4]) -> bool {
+    ao[1] + ao[3] > ao[0] + ao[2]
+}
+
+impl HeightChunk {
+    fn neighbour(c: (usize, usize, usize), dir: (i64, i64, i64)) -> Option<(usize, usize, usize)> {
+        let n = (c.0 as i64 + dir.0, c.1 as i64 + dir.1, c.2 as i64 + dir.2);
+        if n.0 < 0 || n.1 < 0 || n.2 < 0 {
+            return None
+        }
+        return Some((n.0 as usize, n.1 as usize, n.2 as usize))
+    }
+
+    // A face shows unless something opaque or the same liquid covers it. Faces on the edge
+    // of the chunk show, apart from the bottom of the world.
+    fn face_visible(&self, block: Block, f: usize, n: Option<(usize, usize, usize)>) -> bool {
+        match n.and_then(|n| self.get(n)) {
+            Some(nb) => !nb.is_solid() && *nb != block,
+            None => f != 3
+        }
+    }
+
+    // Each face is lit by the light in the block it faces
+    fn face_light(&self, n: Option<(usize, usize, usize)>) -> (u8, [u8; 3]) {
+        match n {
+            Some(n) => (self.sky_light(n), self.block_light(n)),
+            None => (MAX_LIGHT, [0, 0, 0])
+        }
+    }
+
+    fn occludes(&self, c: (i64, i64, i64)) -> bool {
+        if c.0 < 0 || c.1 < 0 || c.2 < 0 {
+            return false
+        }
+        return self.get((c.0 as usize, c.1 as usize, c.2 as usize)).is_some_and(|b| b.is_solid())
+    }
+
+    // Ambient occlusion at each corner of a face, from 0 (fully hidden) to 1 (open), counting
+    // the two blocks along the edges next to the corner and the one diagonally across it
+    fn face_occlusion(&self, c: (usize, usize, usize), f: usize) -> [f32; 4] {
+        let (n, (u, v)) = (FACES[f], FACE_AXES[f]);
+        let front = (c.0 as i64 + n.0, c.1 as i64 + n.1, c.2 as i64 + n.2);
+        let mut ao = [0.; 4];
+        for (i, (cu, cv)) in CORNERS.iter().enumerate() {
+            let (su, sv) = (cu * 2 - 1, cv * 2 - 1);
+            let side1 = self.occludes((front.0 + u.0 * su, front.1 + u.1 * su, front.2 + u.2 * su));
+            let side2 = self.occludes((front.0 + v.0 * sv, front.1 + v.1 * sv, front.2 + v.2 * sv));
+            let corner = self.occludes((front.0 + u.0 * su + v.0 * sv, front.1 + u.1 * su + v.1 * sv, front.2 + u.2 * su + v.2 * sv));
+            let open = if side1 && side2 { 0 } else { 3 - side1 as u8 - side2 as u8 - corner as u8 };
+            ao[i] = open as f32 / 3.;
+        }
+        return ao
+    }
+
+    pub fn faces(&self) -> Vec<FaceInstance> {
+        let mut faces = Vec::new();
+        for (x, row) in self.data.iter().enumerate() {
+            for (y, col) in row.iter().enumerate() {
+                for (z, cell) in col.iter().enumerate() {
+                    if *cell == Block::Air {
+                        continue
+                    }
+                    let height = if cell.is_liquid() { fluid::surface(self.levels[x][y][z]) } else { 1. };
+                    for (f, dir) in FACES.iter().enumerate() {
+                        let n = Self::neighbour((x, y, z), *dir);
+                        if self.face_visible(*cell, f, n) {
+                            let ao = self.face_occlusion((x, y, z), f);
+                            faces.push(face([x as f32, y as f32, z as f32], f, *cell, height, self.face_light(n), ao));
+                        }
+                    }
+                }
+            }
+        }
+        return faces
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOP: usize = 2;
+
+    // The top face of a block in a stone floor, with blocks placed on the floor around it
+    fn top_occlusion(around: &[(usize, usize, usize)]) -> [f32; 4] {
+        let mut chunk = HeightChunk::for_each(|_, y, _| if y < 2 { Block::Stone } else { Block::Air });
+        for c in around {
+            chunk.set(*c, Block::Stone, 0);
+        }
+        return chunk.face_occlusion((5, 1, 5), TOP)
+    }
+
+    #[test]
+    fn corner_between_two_solid_sides_is_fully_occluded() {
+        let ao = top_occlusion(&[(6, 2, 5), (5, 2, 6)]);
+        assert_eq!(ao, [1., 2. / 3., 0., 2. / 3.]);
+        // The diagonal block doesn't make it any darker
+        assert_eq!(top_occlusion(&[(6, 2, 5), (5, 2, 6), (6, 2, 6)]), ao);
+    }
+
+    #[test]
+    fn single_side_darkens_the_corners_along_it() {
+        assert_eq!(top_occlusion(&[(6, 2, 5)]), [1., 2. / 3., 2. / 3., 1.]);
+    }
+
+    #[test]
+    fn open_corners_are_lit() {
+        assert_eq!(top_occlusion(&[]), NO_OCCLUSION);
+        assert_eq!(top_occlusion(&[(6, 2, 6)]), [1., 1., 2. / 3., 1.]);
+    }
+
+    #[test]
+    fn quads_flip_to_split_along_the_brighter_diagonal() {
+        let ao = top_occlusion(&[(6, 2, 5), (5, 2, 6)]);
+        assert!(flips(ao));
+        assert!(flips(top_occlusion(&[(6, 2, 6)])));
+        assert!(!flips(top_occlusion(&[(6, 2, 5)])));
+        assert!(!flips(NO_OCCLUSION));
+        assert!(!flips([0., 1., 1., 0.]));
+
+        assert_eq!(face([0.; 3], TOP, Block::Stone, 1., (0, [0; 3]), ao)[FACE_SIZE - 1], 1.);
+        assert_eq!(face([0.; 3], TOP, Block::Stone, 1., (0, [0; 3]), NO_OCCLUSION)[FACE_SIZE - 1], 0.);
+    }
+}
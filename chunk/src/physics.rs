@@ -0,0 +1,571 @@
+use cgmath::{Point3, Vector3, Zero};
+use crate::{fluid, Block, HeightChunk};
+
+// Block (x, y, z) is centred on that point, so it fills x - 0.5 to x + 0.5 along each axis
+pub const GRAVITY: f32 = 28.;
+pub const TERMINAL: f32 = 50.;
+pub const JUMP: f32 = 9.;
+
+pub const PLAYER_WIDTH: f32 = 0.6;
+pub const PLAYER_HEIGHT: f32 = 1.8;
+pub const EYE_HEIGHT: f32 = 1.6;
+pub const CROUCH_HEIGHT: f32 = 1.5;
+pub const CROUCH_EYE_HEIGHT: f32 = 1.3;
+
+// In liquids bodies sink slowly, rise while swimming up and lose speed to drag
+pub const SWIM_GRAVITY: f32 = 6.;
+pub const SWIM_SINK: f32 = 3.;
+pub const SWIM_ACCEL: f32 = 20.;
+pub const SWIM_RISE: f32 = 4.;
+
+// On climbable blocks bodies go up at CLIMB_SPEED and slide down no faster than CLIMB_FALL
+pub const CLIMB_SPEED: f32 = 3.;
+pub const CLIMB_FALL: f32 = 2.;
+// Players walk up single blocks without jumping
+pub const STEP_HEIGHT: f32 = 1.;
+
+// Slack for rounding when deciding whether a box is touching a block or inside it
+const EPSILON: f32 = 0.001;
+// A box is standing on something if there is a block at most this far below it
+const SUPPORT: f32 = 0.1;
+// Sneaking cuts movement short in steps this long until the body stays over a block
+const SNEAK_STEP: f32 = 0.05;
+
+type Cell = (i64, i64, i64);
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>
+}
+impl Aabb {
+    pub fn block(c: Cell) -> Self {
+        let centre = Point3::new(c.0 as f32, c.1 as f32, c.2 as f32);
+        return Self { min: centre - Vector3::new(0.5, 0.5, 0.5), max: centre + Vector3::new(0.5, 0.5, 0.5) }
+    }
+
+    pub fn translate(&self, d: Vector3<f32>) -> Self {
+        Self { min: self.min + d, max: self.max + d }
+    }
+
+    // Boxes that only touch don't overlap
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        (0..3).all(|a| self.min[a] < other.max[a] - EPSILON && self.max[a] > other.min[a] + EPSILON)
+    }
+}
+
+// Block containing coordinate v along one axis
+fn cell(v: f32) -> i64 {
+    (v + 0.5).floor() as i64
+}
+
+// Bodies collide with solid blocks and with the floor of the world. Sideways the chunk
+// just ends, as the terrain does.
+pub fn solid(chunk: &HeightChunk, c: Cell) -> bool {
+    if c.1 < 0 {
+        return true
+    }
+    if c.0 < 0 || c.2 < 0 {
+        return false
+    }
+    return chunk.get((c.0 as usize, c.1 as usize, c.2 as usize)).is_some_and(|b| b.is_solid())
+}
+
+// How far bb can move along axis, up to d, before it runs into a solid block. Every block in
+// the swept region is checked, so fast bodies can't tunnel through thin walls.
+pub fn clip(chunk: &HeightChunk, bb: &Aabb, axis: usize, d: f32) -> f32 {
+    if d == 0. {
+        return 0.
+    }
+    let (mut lo, mut hi) = (bb.min, bb.max);
+    if d > 0. { hi[axis] += d } else { lo[axis] += d }
+
+    let mut allowed = d;
+    for x in cell(lo.x)..=cell(hi.x) {
+        for y in cell(lo.y)..=cell(hi.y) {
+            for z in cell(lo.z)..=cell(hi.z) {
+                if !solid(chunk, (x, y, z)) {
+                    continue
+                }
+                let b = Aabb::block((x, y, z));
+                let beside = (0..3).filter(|a| *a != axis).all(|a| bb.min[a] < b.max[a] - EPSILON && bb.max[a] > b.min[a] + EPSILON);
+                if !beside {
+                    continue
+                }
+                if d > 0. && b.min[axis] >= bb.max[axis] - EPSILON {
+                    allowed = allowed.min((b.min[axis] - bb.max[axis]).max(0.));
+                }
+                if d < 0. && b.max[axis] <= bb.min[axis] + EPSILON {
+                    allowed = allowed.max((b.max[axis] - bb.min[axis]).min(0.));
+                }
+            }
+        }
+    }
+    return allowed
+}
+
+// Fraction of its vertical speed a body loses per second in a liquid
+fn drag(liquid: Block) -> f32 {
+    if liquid == Block::Lava { 6. } else { 3. }
+}
+
+// The liquid at point p, if p is below its surface
+pub fn liquid_at(chunk: &HeightChunk, p: Point3<f32>) -> Option<Block> {
+    if p.x < -0.5 || p.y < -0.5 || p.z < -0.5 {
+        return None
+    }
+    let c = HeightChunk::to_index(p);
+    let b = *chunk.get(c)?;
+    if !b.is_liquid() || p.y > c.1 as f32 - 0.5 + fluid::surface(chunk.level(c)) {
+        return None
+    }
+    return Some(b)
+}
+
+pub fn supported(chunk: &HeightChunk, bb: &Aabb) -> bool {
+    clip(chunk, bb, 1, -SUPPORT) > -SUPPORT
+}
+
+fn shrink(v: f32) -> f32 {
+    if v.abs() <= SNEAK_STEP { 0. } else { v - SNEAK_STEP * v.signum() }
+}
+
+// Blocks inside the chunk that bb overlaps or touches
+fn cells(bb: &Aabb) -> impl Iterator<Item = (usize, usize, usize)> {
+    let (lo, hi) = (bb.min, bb.max);
+    (cell(lo.x)..=cell(hi.x)).flat_map(move |x| (cell(lo.y)..=cell(hi.y)).flat_map(move |y| (cell(lo.z)..=cell(hi.z)).map(move |z| (x, y, z))))
+        .filter(|c| c.0 >= 0 && c.1 >= 0 && c.2 >= 0)
+        .map(|c| (c.0 as usize, c.1 as usize, c.2 as usize))
+}
+
+fn axis(a: usize) -> Vector3<f32> {
+    let mut v = Vector3::zero();
+    v[a] = 1.;
+    return v
+}
+
+// Something that moves through the world as an upright box
+#[derive(Copy, Clone, Debug)]
+pub struct Body {
+    // Centre of the bottom of the box
+    pub pos: Point3<f32>,
+    pub vel: Vector3<f32>,
+    pub width: f32,
+    pub height: f32,
+    pub on_ground: bool,
+    // Ledges up to this high are stepped onto when walking into them on the ground, 0 turns that off
+    pub step_height: f32,
+    // How far the last move was lifted by stepping up a ledge
+    pub stepped: f32,
+    // Sneaking bodies on the ground don't walk off the edge of what they stand on
+    pub sneaking: bool,
+    // Downward speed the body had when it landed in the last move, 0 if it didn't land
+    pub impact: f32
+}
+impl Body {
+    pub fn player(pos: Point3<f32>) -> Self {
+        Self { pos, vel: Vector3::zero(), width: PLAYER_WIDTH, height: PLAYER_HEIGHT, on_ground: false, step_height: STEP_HEIGHT, stepped: 0., sneaking: false, impact: 0. }
+    }
+    // A cube of the given size, such as a dropped item, which doesn't step up ledges
+    pub fn cube(pos: Point3<f32>, size: f32) -> Self {
+        Self { pos, vel: Vector3::zero(), width: size, height: size, on_ground: false, step_height: 0., stepped: 0., sneaking: false, impact: 0. }
+    }
+
+    pub fn aabb(&self) -> Aabb {
+        let half = self.width / 2.;
+        return Aabb {
+            min: Point3::new(self.pos.x - half, self.pos.y, self.pos.z - half),
+            max: Point3::new(self.pos.x + half, self.pos.y + self.height, self.pos.z + half)
+        }
+    }
+
+    // Changes the height of the box, if there is room above it. Returns whether it could.
+    pub fn resize(&mut self, chunk: &HeightChunk, height: f32) -> bool {
+        let grow = height - self.height;
+        if grow > 0. && clip(chunk, &self.aabb(), 1, grow) < grow {
+            return false
+        }
+        self.height = height;
+        return true
+    }
+
+    pub fn apply_gravity(&mut self, dt: f32) {
+        self.vel.y = (self.vel.y - GRAVITY * dt).max(-TERMINAL);
+    }
+
+    // The liquid any part of the body is in, below its surface. Lava wins over water.
+    pub fn liquid(&self, chunk: &HeightChunk) -> Option<Block> {
+        let bb = self.aabb();
+        let mut found = None;
+        for c in cells(&bb) {
+            let b = match chunk.get(c) {
+                Some(b) if b.is_liquid() => *b,
+                _ => continue
+            };
+            if c.1 as f32 - 0.5 + fluid::surface(chunk.level(c)) > bb.min.y && found != Some(Block::Lava) {
+                found = Some(b);
+            }
+        }
+        return found
+    }
+
+    // Every block the body is inside of
+    pub fn touching<'a>(&self, chunk: &'a HeightChunk) -> impl Iterator<Item = Block> + 'a {
+        let bb = self.aabb();
+        return cells(&bb)
+            .filter(move |c| bb.overlaps(&Aabb::block((c.0 as i64, c.1 as i64, c.2 as i64))))
+            .filter_map(move |c| chunk.get(c).copied())
+    }
+
+    pub fn climbing(&self, chunk: &HeightChunk) -> bool {
+        return self.touching(chunk).any(|b| b.climbable())
+    }
+
+    // Replaces gravity while climbing: up, holding on in place, or sliding down slowly
+    pub fn climb(&mut self, dt: f32, up: bool, hold: bool) {
+        if up {
+            self.vel.y = CLIMB_SPEED;
+        } else if hold {
+            self.vel.y = 0.;
+        } else {
+            self.apply_gravity(dt);
+            self.vel.y = self.vel.y.max(-CLIMB_FALL);
+        }
+    }
+
+    // Buoyancy and drag in place of gravity, rising while the swimmer wants to
+    pub fn swim(&mut self, dt: f32, liquid: Block, rising: bool) {
+        self.vel.y *= (1. - drag(liquid) * dt).max(0.);
+        if rising {
+            self.vel.y = (self.vel.y + SWIM_ACCEL * dt).min(SWIM_RISE);
+        } else {
+            self.vel.y = (self.vel.y - SWIM_GRAVITY * dt).max(-SWIM_SINK);
+        }
+    }
+
+    // Moves bb by d along the given axes in turn, stopping each at the first block in the way
+    fn slide(chunk: &HeightChunk, bb: Aabb, d: Vector3<f32>, axes: &[usize]) -> Vector3<f32> {
+        let mut bb = bb;
+        let mut moved = Vector3::zero();
+        for a in axes {
+            let m = clip(chunk, &bb, *a, d[*a]);
+            bb = bb.translate(axis(*a) * m);
+            moved[*a] = m;
+        }
+        return moved
+    }
+
+    // Moves the body by d, one axis at a time so it slides along whatever it hits. Vertical
+    // movement goes first, so landing and bumping a ceiling resolve before walking.
+    // Returns how far it actually moved.
+    pub fn move_by(&mut self, chunk: &HeightChunk, d: Vector3<f32>) -> Vector3<f32> {
+        let d = if self.sneaking && self.on_ground { self.keep_footing(chunk, d) } else { d };
+        let start = self.aabb();
+        let mut moved = Self::slide(chunk, start, d, &[1, 0, 2]);
+        self.stepped = 0.;
+
+        // Walking into a ledge: lift the body as far as step_height allows, move it across,
+        // then settle it back down. That wins if it gets further than sliding did.
+        let blocked = moved.x != d.x || moved.z != d.z;
+        if self.on_ground && blocked && self.step_height > 0. && d.y <= 0. {
+            let up = clip(chunk, &start, 1, self.step_height);
+            let lifted = start.translate(axis(1) * up);
+            let across = Self::slide(chunk, lifted, Vector3::new(d.x, 0., d.z), &[0, 2]);
+            let down = clip(chunk, &lifted.translate(across), 1, d.y - up);
+            let stepped = Vector3::new(across.x, up + down, across.z);
+            let further = stepped.x * stepped.x + stepped.z * stepped.z > moved.x * moved.x + moved.z * moved.z + EPSILON;
+            if further && stepped.y > moved.y {
+                self.stepped = stepped.y - moved.y;
+                moved = stepped;
+            }
+        }
+
+        self.impact = if d.y < 0. && moved.y > d.y { -self.vel.y } else { 0. };
+        for a in 0..3 {
+            if moved[a] != d[a] {
+                self.vel[a] = 0.;
+            }
+        }
+        self.on_ground = d.y < 0. && moved.y > d.y;
+        self.pos += moved;
+        return moved
+    }
+
+    // Shortens the horizontal part of d until the body would still be standing on something,
+    // first along each axis, then both together for outside corners
+    fn keep_footing(&self, chunk: &HeightChunk, d: Vector3<f32>) -> Vector3<f32> {
+        let bb = self.aabb();
+        let standing = |dx: f32, dz: f32| supported(chunk, &bb.translate(Vector3::new(dx, 0., dz)));
+        let (mut dx, mut dz) = (d.x, d.z);
+        while dx != 0. && !standing(dx, 0.) {
+            dx = shrink(dx);
+        }
+        while dz != 0. && !standing(0., dz) {
+            dz = shrink(dz);
+        }
+        while dx != 0. && dz != 0. && !standing(dx, dz) {
+            dx = shrink(dx);
+            dz = shrink(dz);
+        }
+        return Vector3::new(dx, d.y, dz)
+    }
+
+    pub fn step(&mut self, chunk: &HeightChunk, dt: f32) -> Vector3<f32> {
+        let d = self.vel * dt;
+        return self.move_by(chunk, d)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: f32 = 1. / 60.;
+
+    // A stone floor at y = 0, so its top is at 0.5, with stone blocks added at cells
+    fn chunk_with(cells: &[(usize, usize, usize)]) -> HeightChunk {
+        HeightChunk::for_each(|x, y, z| if y == 0 || cells.contains(&(x, y, z)) { Block::Stone } else { Block::Air })
+    }
+
+    // A player standing on the floor at x, z
+    fn standing(chunk: &HeightChunk, x: f32, z: f32) -> Body {
+        let mut body = Body::player(Point3::new(x, 0.5, z));
+        body.move_by(chunk, Vector3::new(0., -0.01, 0.));
+        assert!(body.on_ground);
+        return body
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 0.01
+    }
+
+    #[test]
+    fn falls_and_lands_on_the_floor() {
+        let chunk = chunk_with(&[]);
+        let mut body = Body::player(Point3::new(8., 5., 8.));
+        let mut impact = 0.;
+        for _ in 0..120 {
+            body.apply_gravity(DT);
+            body.step(&chunk, DT);
+            impact = f32::max(impact, body.impact);
+        }
+        assert!(close(body.pos.y, 0.5), "feet at {}", body.pos.y);
+        assert!(body.on_ground);
+        assert!(impact > 0.);
+        assert_eq!(body.vel.y, 0.);
+    }
+
+    #[test]
+    fn not_on_ground_in_the_air() {
+        let chunk = chunk_with(&[]);
+        let mut body = Body::player(Point3::new(8., 3., 8.));
+        body.move_by(&chunk, Vector3::new(0., -0.1, 0.));
+        assert!(!body.on_ground);
+        assert!(close(body.pos.y, 2.9));
+    }
+
+    #[test]
+    fn slides_along_a_wall() {
+        let wall: Vec<_> = (0..16).flat_map(|z| (1..4).map(move |y| (10, y, z))).collect();
+        let chunk = chunk_with(&wall);
+        let mut body = standing(&chunk, 8., 8.);
+        let moved = body.move_by(&chunk, Vector3::new(5., -0.01, 1.));
+        // Stopped with its side against the wall face at 9.5, but still moving along it
+        assert!(close(body.pos.x, 9.5 - PLAYER_WIDTH / 2.), "x at {}", body.pos.x);
+        assert!(close(moved.z, 1.));
+        assert_eq!(body.vel.x, 0.);
+    }
+
+    #[test]
+    fn bumps_its_head_on_a_ceiling() {
+        let chunk = chunk_with(&[(8, 3, 8)]);
+        let mut body = standing(&chunk, 8., 8.);
+        body.vel.y = JUMP;
+        let moved = body.move_by(&chunk, Vector3::new(0., 1., 0.));
+        // The head stops at the underside of the block, 2.5
+        assert!(close(moved.y, 2.5 - 0.5 - PLAYER_HEIGHT), "moved {}", moved.y);
+        assert_eq!(body.vel.y, 0.);
+        assert!(!body.on_ground);
+    }
+
+    #[test]
+    fn fast_bodies_do_not_tunnel() {
+        let chunk = chunk_with(&[(10, 1, 8), (10, 2, 8)]);
+        let mut body = standing(&chunk, 8., 8.);
+        body.step_height = 0.;
+        body.move_by(&chunk, Vector3::new(100., -0.01, 0.));
+        assert!(close(body.pos.x, 9.5 - PLAYER_WIDTH / 2.), "x at {}", body.pos.x);
+
+        let mut falling = Body::player(Point3::new(4., 10., 4.));
+        falling.move_by(&chunk, Vector3::new(0., -TERMINAL * 10., 0.));
+        assert!(close(falling.pos.y, 0.5));
+    }
+
+    #[test]
+    fn steps_up_single_blocks_only() {
+        let chunk = chunk_with(&[(9, 1, 8)]);
+        let mut body = standing(&chunk, 8., 8.);
+        body.move_by(&chunk, Vector3::new(1., -0.01, 0.));
+        assert!(close(body.pos.y, 1.5), "feet at {}", body.pos.y);
+        assert!(close(body.stepped, 1.));
+        assert!(close(body.pos.x, 9.));
+
+        let wall = chunk_with(&[(9, 1, 8), (9, 2, 8)]);
+        let mut body = standing(&wall, 8., 8.);
+        body.move_by(&wall, Vector3::new(1., -0.01, 0.));
+        assert!(close(body.pos.y, 0.5));
+        assert!(close(body.pos.x, 8.5 - PLAYER_WIDTH / 2.));
+        assert_eq!(body.stepped, 0.);
+    }
+
+    #[test]
+    fn sneaking_keeps_footing_at_edges() {
+        // Floor only up to x = 8, whose edge is at 8.5
+        let ledge = HeightChunk::for_each(|x, y, _| if y == 0 && x <= 8 { Block::Stone } else { Block::Air });
+        let mut body = standing(&ledge, 8., 8.);
+        body.sneaking = true;
+        for _ in 0..10 {
+            body.move_by(&ledge, Vector3::new(0.3, -0.01, 0.3));
+        }
+        assert!(body.on_ground);
+        assert!(supported(&ledge, &body.aabb()));
+        assert!(body.pos.x > 8.5 && body.pos.x <= 8.5 + PLAYER_WIDTH / 2., "x at {}", body.pos.x);
+        assert!(body.pos.z > 9., "sneaking still walks along the edge");
+
+        let mut walker = standing(&ledge, 8., 8.);
+        walker.move_by(&ledge, Vector3::new(1., -0.01, 0.));
+        assert!(!supported(&ledge, &walker.aabb()));
+    }
+
+    // Stone floor with a pool of liquid filling x < 8 from y = 1 to y = depth
+    fn pool(liquid: Block, depth: usize) -> HeightChunk {
+        HeightChunk::for_each(|x, y, _| match y {
+            0 => Block::Stone,
+            y if y <= depth && x < 8 => liquid,
+            _ => Block::Air
+        })
+    }
+
+    // Swims for n steps, returning the body
+    fn swim_for(chunk: &HeightChunk, mut body: Body, rising: bool, n: usize) -> Body {
+        for _ in 0..n {
+            let liquid = body.liquid(chunk).expect("left the liquid");
+            body.swim(DT, liquid, rising);
+            body.step(chunk, DT);
+        }
+        return body
+    }
+
+    #[test]
+    fn liquid_is_found_below_its_surface() {
+        let mut chunk = pool(Block::Water, 3);
+        // Full blocks reach the top of the block, 3.5 for the top layer
+        assert_eq!(liquid_at(&chunk, Point3::new(4., 2., 4.)), Some(Block::Water));
+        assert_eq!(liquid_at(&chunk, Point3::new(4., 3.4, 4.)), Some(Block::Water));
+        assert_eq!(liquid_at(&chunk, Point3::new(4., 3.6, 4.)), None);
+        assert_eq!(liquid_at(&chunk, Point3::new(10., 2., 4.)), None);
+        assert_eq!(liquid_at(&chunk, Point3::new(-4., 2., 4.)), None);
+
+        // Flowing liquid sits lower in its block
+        chunk.set((10, 1, 4), Block::Lava, 4);
+        assert_eq!(liquid_at(&chunk, Point3::new(10., 0.9, 4.)), Some(Block::Lava));
+        assert_eq!(liquid_at(&chunk, Point3::new(10., 1.1, 4.)), None);
+    }
+
+    #[test]
+    fn bodies_are_in_the_liquid_they_dip_into_and_lava_wins() {
+        let mut chunk = pool(Block::Water, 3);
+        assert_eq!(Body::player(Point3::new(4., 3., 4.)).liquid(&chunk), Some(Block::Water));
+        assert_eq!(Body::player(Point3::new(4., 3.6, 4.)).liquid(&chunk), None);
+        assert_eq!(Body::player(Point3::new(10., 0.5, 4.)).liquid(&chunk), None);
+
+        chunk.set((4, 4, 4), Block::Lava, 0);
+        assert_eq!(Body::player(Point3::new(4., 3., 4.)).liquid(&chunk), Some(Block::Lava));
+    }
+
+    #[test]
+    fn swimmers_sink_slowly_and_rise_while_jump_is_held() {
+        let chunk = pool(Block::Water, 12);
+        let start = Body::player(Point3::new(4., 6., 4.));
+
+        let sunk = swim_for(&chunk, start, false, 60);
+        // Far less than the 14 blocks a second of falling through air would take it
+        assert!(sunk.pos.y < 6. && sunk.pos.y > 4., "feet at {}", sunk.pos.y);
+        assert!(sunk.vel.y < 0. && sunk.vel.y >= -SWIM_SINK, "sinking at {}", sunk.vel.y);
+
+        let risen = swim_for(&chunk, start, true, 60);
+        assert!(risen.pos.y > 6., "feet at {}", risen.pos.y);
+        assert!(close(risen.vel.y, SWIM_RISE), "rising at {}", risen.vel.y);
+    }
+
+    #[test]
+    fn lava_drags_harder_than_water() {
+        let (water, lava) = (pool(Block::Water, 12), pool(Block::Lava, 12));
+        let mut start = Body::player(Point3::new(4., 2., 4.));
+        start.vel.y = 8.;
+        let (w, l) = (swim_for(&water, start, false, 10), swim_for(&lava, start, false, 10));
+        assert!(l.vel.y < w.vel.y && l.pos.y < w.pos.y, "lava {} water {}", l.vel.y, w.vel.y);
+
+        // Diving in, rising swimmers turn around sooner in lava
+        start.pos.y = 8.;
+        start.vel.y = -10.;
+        let (w, l) = (swim_for(&water, start, true, 10), swim_for(&lava, start, true, 10));
+        assert!(l.vel.y > w.vel.y && l.pos.y > w.pos.y, "lava {} water {}", l.vel.y, w.vel.y);
+    }
+
+    // Moves the way a walking player does: climbing while inside a ladder, falling otherwise
+    fn climb_for(chunk: &HeightChunk, mut body: Body, up: bool, hold: bool, n: usize) -> Body {
+        for _ in 0..n {
+            if body.climbing(chunk) {
+                body.climb(DT, up, hold);
+            } else {
+                body.apply_gravity(DT);
+            }
+            body.step(chunk, DT);
+        }
+        return body
+    }
+
+    // A ladder from the floor up to y = 4
+    fn ladder() -> HeightChunk {
+        HeightChunk::for_each(|x, y, z| match (x, y, z) {
+            (_, 0, _) => Block::Stone,
+            (8, 1..=4, 8) => Block::Ladder,
+            _ => Block::Air
+        })
+    }
+
+    #[test]
+    fn climbs_up_ladders() {
+        let chunk = ladder();
+        let body = standing(&chunk, 8., 8.);
+        assert!(body.climbing(&chunk));
+        assert!(!standing(&chunk, 10., 8.).climbing(&chunk));
+
+        let body = climb_for(&chunk, body, true, false, 30);
+        assert!(close(body.pos.y, 0.5 + CLIMB_SPEED / 2.), "feet at {}", body.pos.y);
+        assert_eq!(body.vel.y, CLIMB_SPEED);
+    }
+
+    #[test]
+    fn holding_on_stops_in_place() {
+        let chunk = ladder();
+        let mut body = Body::player(Point3::new(8., 2.5, 8.));
+        body.vel.y = -5.;
+        let body = climb_for(&chunk, body, false, true, 30);
+        assert!(close(body.pos.y, 2.5), "feet at {}", body.pos.y);
+        assert_eq!(body.vel.y, 0.);
+    }
+
+    #[test]
+    fn slides_down_slowly_and_falls_once_off_the_ladder() {
+        let chunk = ladder();
+        let body = climb_for(&chunk, Body::player(Point3::new(8., 3.5, 8.)), false, false, 30);
+        assert!(body.pos.y < 3.5 && body.vel.y >= -CLIMB_FALL, "falling at {}", body.vel.y);
+
+        let mut off = body;
+        off.pos.x = 10.;
+        let off = climb_for(&chunk, off, false, false, 10);
+        assert!(off.vel.y < -CLIMB_FALL, "falling at {}", off.vel.y);
+    }
+}
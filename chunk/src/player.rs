@@ -0,0 +1,122 @@
+use crate::Block;
+use crate::inventory::Inventory;
+use crate::item::Item;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum GameMode {
+    // Blocks take time to break and drop items, and the player can get hurt
+    #[default]
+    Survival,
+    // Blocks break at once without dropping anything, placing blocks never runs out of
+    // them, and the player can fly
+    Creative
+}
+impl GameMode {
+    pub const ALL: [GameMode; 2] = [GameMode::Survival, GameMode::Creative];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GameMode::Survival => "survival",
+            GameMode::Creative => "creative"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<GameMode> {
+        Self::ALL.iter().copied().find(|m| m.name() == name)
+    }
+
+    pub fn takes_damage(self) -> bool {
+        self == GameMode::Survival
+    }
+    pub fn instant_break(self) -> bool {
+        self == GameMode::Creative
+    }
+    pub fn drops_items(self) -> bool {
+        self == GameMode::Survival
+    }
+    pub fn uses_up_blocks(self) -> bool {
+        self == GameMode::Survival
+    }
+    pub fn can_fly(self) -> bool {
+        self == GameMode::Creative
+    }
+}
+
+// Everything about the player that is saved with the world
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Player {
+    pub game_mode: GameMode,
+    pub inventory: Inventory
+}
+impl Player {
+    pub fn new() -> Self {
+        Self { game_mode: GameMode::Survival, inventory: Inventory::new() }
+    }
+
+    // The block in the selected hotbar slot, taken out to be placed. Survival uses one up
+    // from the stack, creative leaves it as it is.
+    pub fn take_block(&mut self) -> Option<Block> {
+        let block = match self.inventory.held()?.item {
+            Item::Block(b) => b,
+            Item::Tool(_) => return None
+        };
+        if self.game_mode.uses_up_blocks() {
+            self.inventory.take(self.inventory.selected, 1);
+        }
+        return Some(block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::breaking::Tool;
+    use crate::inventory::SLOTS;
+    use crate::item::ItemStack;
+
+    fn holding(game_mode: GameMode, stack: ItemStack) -> Player {
+        let mut player = Player { game_mode, inventory: Inventory::new() };
+        player.inventory.add(stack);
+        return player
+    }
+
+    #[test]
+    fn creative_never_runs_out_of_blocks() {
+        let mut player = holding(GameMode::Creative, ItemStack::new(Item::Block(Block::Stone), 1));
+        for _ in 0..100 {
+            assert_eq!(player.take_block(), Some(Block::Stone));
+        }
+        assert_eq!(player.inventory.held(), Some(ItemStack::new(Item::Block(Block::Stone), 1)));
+    }
+
+    #[test]
+    fn survival_uses_up_the_held_stack() {
+        let mut player = holding(GameMode::Survival, ItemStack::new(Item::Block(Block::Dirt), 2));
+        assert_eq!(player.take_block(), Some(Block::Dirt));
+        assert_eq!(player.inventory.held(), Some(ItemStack::new(Item::Block(Block::Dirt), 1)));
+        assert_eq!(player.take_block(), Some(Block::Dirt));
+        assert_eq!(player.inventory.held(), None);
+        assert_eq!(player.take_block(), None);
+    }
+
+    #[test]
+    fn tools_are_not_placed() {
+        for mode in GameMode::ALL {
+            let mut player = holding(mode, ItemStack::new(Item::Tool(Tool::Pickaxe), 1));
+            assert_eq!(player.take_block(), None);
+            assert!(player.inventory.held().is_some());
+        }
+    }
+
+    // Survival only holds what fits: full stacks in every slot, and nothing more
+    #[test]
+    fn survival_inventory_is_limited_to_its_slots_and_stacks() {
+        let stone = Item::Block(Block::Stone);
+        let max = stone.max_stack();
+        let mut player = holding(GameMode::Survival, ItemStack::new(stone, max * SLOTS as u32 + 5));
+        assert!(player.inventory.slots.iter().all(|s| *s == Some(ItemStack::new(stone, max))));
+        assert_eq!(player.inventory.add(ItemStack::new(stone, 1)), Some(ItemStack::new(stone, 1)));
+        player.take_block();
+        assert_eq!(player.inventory.held(), Some(ItemStack::new(stone, max - 1)));
+    }
+}
@@ -0,0 +1,218 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::{Block, HeightChunk, CHUNK, HEIGHT};
+use crate::inventory::SLOTS;
+use crate::item::{Item, ItemStack};
+use crate::player::{GameMode, Player};
+
+// A world is a directory holding one file per chunk, named c.<cx>.<cz>.bin, level.txt and
+// player.txt
+const MAGIC: &[u8; 4] = b"TWC2";
+// Chunks saved before blocks had levels
+const MAGIC_V1: &[u8; 4] = b"TWC1";
+const BLOCKS: usize = CHUNK * HEIGHT * CHUNK;
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+impl HeightChunk {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(MAGIC.len() + BLOCKS * 2);
+        bytes.extend_from_slice(MAGIC);
+        for row in self.data.iter() {
+            for col in row.iter() {
+                bytes.extend(col.iter().map(|b| *b as u8));
+            }
+        }
+        for row in self.levels.iter() {
+            for col in row.iter() {
+                bytes.extend_from_slice(col);
+            }
+        }
+        return bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<HeightChunk> {
+        let body = &bytes[MAGIC.len().min(bytes.len())..];
+        let (blocks, levels) = match &bytes[..MAGIC.len().min(bytes.len())] {
+            m if m == MAGIC && body.len() == BLOCKS * 2 => body.split_at(BLOCKS),
+            m if m == MAGIC_V1 && body.len() == BLOCKS => (body, &[][..]),
+            _ => return Err(invalid("not a chunk file"))
+        };
+        for b in blocks {
+            Block::from_id(*b).ok_or_else(|| invalid("unknown block id"))?;
+        }
+        let index = |x: usize, y: usize, z: usize| (x * HEIGHT + y) * CHUNK + z;
+        let mut chunk = HeightChunk::for_each(|x, y, z| Block::from_id(blocks[index(x, y, z)]).unwrap());
+        if !levels.is_empty() {
+            for x in 0..CHUNK {
+                for y in 0..HEIGHT {
+                    chunk.levels[x][y].copy_from_slice(&levels[index(x, y, 0)..index(x, y, CHUNK)]);
+                }
+            }
+        }
+        return Ok(chunk)
+    }
+}
+
+pub fn chunk_path(dir: &Path, cx: i32, cz: i32) -> PathBuf {
+    dir.join(format!("c.{}.{}.bin", cx, cz))
+}
+
+pub fn write_chunk(dir: &Path, cx: i32, cz: i32, chunk: &HeightChunk) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    return fs::write(chunk_path(dir, cx, cz), chunk.to_bytes())
+}
+
+// Ok(None) when the chunk has never been saved
+pub fn read_chunk(dir: &Path, cx: i32, cz: i32) -> io::Result<Option<HeightChunk>> {
+    match fs::read(chunk_path(dir, cx, cz)) {
+        Ok(bytes) => HeightChunk::from_bytes(&bytes).map(Some),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e)
+    }
+}
+
+// World-wide state that isn't part of any chunk, saved as "key value..." lines in level.txt.
+// Keys this version doesn't know are skipped.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Level {
+    // Block the player's feet are in when spawning
+    pub spawn: Option<(usize, usize, usize)>,
+    // Seed the world was created with, which later dimensions are generated from
+    pub seed: Option<u32>
+}
+impl Level {
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        if let Some((x, y, z)) = self.spawn {
+            text += &format!("spawn {} {} {}\n", x, y, z);
+        }
+        if let Some(seed) = self.seed {
+            text += &format!("seed {}\n", seed);
+        }
+        return text
+    }
+
+    pub fn from_text(text: &str) -> io::Result<Level> {
+        let mut level = Level::default();
+        for line in text.lines() {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words[..] {
+                ["spawn", x, y, z] => {
+                    let n = |s: &str| s.parse::<usize>().map_err(|_| invalid("bad spawn point"));
+                    level.spawn = Some((n(x)?, n(y)?, n(z)?));
+                },
+                ["seed", seed] => level.seed = Some(seed.parse().map_err(|_| invalid("bad seed"))?),
+                _ => {}
+            }
+        }
+        return Ok(level)
+    }
+}
+
+pub fn write_level(dir: &Path, level: &Level) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    return fs::write(dir.join("level.txt"), level.to_text())
+}
+
+// The default level when the world has never been saved
+pub fn read_level(dir: &Path) -> io::Result<Level> {
+    match fs::read_to_string(dir.join("level.txt")) {
+        Ok(text) => Level::from_text(&text),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Level::default()),
+        Err(e) => Err(e)
+    }
+}
+
+// The player is saved in player.txt in the same "key value..." form as the level, with a
+// "slot <index> <item> <count>" line for each slot that isn't empty
+impl Player {
+    pub fn to_text(&self) -> String {
+        let mut text = format!("gamemode {}\n", self.game_mode.name());
+        text += &format!("selected {}\n", self.inventory.selected);
+        for (i, slot) in self.inventory.slots.iter().enumerate() {
+            if let Some(stack) = slot {
+                text += &format!("slot {} {} {}\n", i, stack.item.name(), stack.count);
+            }
+        }
+        return text
+    }
+
+    pub fn from_text(text: &str) -> io::Result<Player> {
+        let mut player = Player::new();
+        for line in text.lines() {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words[..] {
+                ["gamemode", name] => player.game_mode = GameMode::from_name(name).ok_or_else(|| invalid("unknown game mode"))?,
+                ["selected", i] => player.inventory.select(i.parse().map_err(|_| invalid("bad hotbar slot"))?),
+                ["slot", i, name, count] => {
+                    let i = i.parse::<usize>().ok().filter(|i| *i < SLOTS).ok_or_else(|| invalid("bad slot"))?;
+                    let item = Item::from_name(name).ok_or_else(|| invalid("unknown item"))?;
+                    let count = count.parse::<u32>().ok().filter(|c| *c > 0).ok_or_else(|| invalid("bad item count"))?;
+                    player.inventory.slots[i] = Some(ItemStack::new(item, count.min(item.max_stack())));
+                },
+                _ => {}
+            }
+        }
+        return Ok(player)
+    }
+}
+
+pub fn write_player(dir: &Path, player: &Player) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    return fs::write(dir.join("player.txt"), player.to_text())
+}
+
+// A new player when the world has never been saved
+pub fn read_player(dir: &Path) -> io::Result<Player> {
+    match fs::read_to_string(dir.join("player.txt")) {
+        Ok(text) => Player::from_text(&text),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Player::new()),
+        Err(e) => Err(e)
+    }
+}
+
+pub fn list_chunks(dir: &Path) -> io::Result<Vec<(i32, i32)>> {
+    let mut chunks = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let name = entry?.file_name();
+        let parts: Vec<&str> = match name.to_str() {
+            Some(name) => name.split('.').collect(),
+            None => continue
+        };
+        if let ["c", cx, cz, "bin"] = parts[..] {
+            if let (Ok(cx), Ok(cz)) = (cx.parse(), cz.parse()) {
+                chunks.push((cx, cz));
+            }
+        }
+    }
+    chunks.sort();
+    return Ok(chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::breaking::Tool;
+
+    #[test]
+    fn player_round_trips_through_text() {
+        let mut player = Player::new();
+        player.game_mode = GameMode::Creative;
+        player.inventory.selected = 5;
+        player.inventory.slots[0] = Some(ItemStack::new(Item::Block(Block::Dirt), 64));
+        player.inventory.slots[5] = Some(ItemStack::new(Item::Tool(Tool::Shovel), 1));
+        player.inventory.slots[SLOTS - 1] = Some(ItemStack::new(Item::Block(Block::Crystal), 7));
+        assert_eq!(Player::from_text(&player.to_text()).unwrap(), player);
+    }
+
+    #[test]
+    fn bad_player_lines_are_errors() {
+        for text in ["gamemode hardcore", "slot 36 stone 1", "slot 0 water 1", "slot 0 stone 0"] {
+            assert_eq!(Player::from_text(text).unwrap_err().kind(), io::ErrorKind::InvalidData, "{}", text);
+        }
+    }
+}
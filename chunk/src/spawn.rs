@@ -0,0 +1,80 @@
+use crate::{HeightChunk, CHUNK};
+
+type Pos = (usize, usize, usize);
+
+// Players can spawn on a column whose top block is solid and harmless. Anything above the
+// top block is air, so there is always room to stand, and water on top makes it unsafe.
+pub fn safe_ground(chunk: &HeightChunk, x: usize, z: usize) -> Option<usize> {
+    let (y, b) = chunk.top(x, z)?;
+    if !b.is_solid() || b.damage() > 0. {
+        return None
+    }
+    return Some(y)
+}
+
+// Where the feet go on the safe column closest to near, searching outward ring by ring
+pub fn find_spawn(chunk: &HeightChunk, near: (usize, usize)) -> Option<Pos> {
+    let near = (near.0.min(CHUNK - 1) as i64, near.1.min(CHUNK - 1) as i64);
+    for r in 0..CHUNK as i64 {
+        for dx in -r..=r {
+            for dz in -r..=r {
+                if dx.abs() != r && dz.abs() != r {
+                    continue
+                }
+                let (x, z) = (near.0 + dx, near.1 + dz);
+                if x < 0 || z < 0 || x >= CHUNK as i64 || z >= CHUNK as i64 {
+                    continue
+                }
+                if let Some(y) = safe_ground(chunk, x as usize, z as usize) {
+                    return Some((x as usize, y + 1, z as usize))
+                }
+            }
+        }
+    }
+    return None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Block;
+
+    // Stone up to y = 3, covered by whatever top gives for the column at x
+    fn ground(top: impl Fn(usize) -> Block) -> HeightChunk {
+        HeightChunk::for_each(|x, y, _| match y {
+            0..=2 => Block::Stone,
+            3 => top(x),
+            _ => Block::Air
+        })
+    }
+
+    #[test]
+    fn spawns_on_solid_ground_with_room_to_stand() {
+        let chunk = ground(|_| Block::Grass);
+        let (x, y, z) = find_spawn(&chunk, (5, 7)).unwrap();
+        assert_eq!((x, y, z), (5, 4, 7));
+        assert!(chunk.get((x, y - 1, z)).unwrap().is_solid());
+        assert_eq!(*chunk.get((x, y, z)).unwrap(), Block::Air);
+        assert_eq!(*chunk.get((x, y + 1, z)).unwrap(), Block::Air);
+    }
+
+    #[test]
+    fn spawns_away_from_water_and_lava() {
+        let chunk = ground(|x| match x {
+            0..=9 => Block::Water,
+            10..=11 => Block::Lava,
+            _ => Block::Stone
+        });
+        let (x, y, z) = find_spawn(&chunk, (0, 40)).unwrap();
+        // The nearest safe columns are 12 out
+        assert_eq!((x, y), (12, 4));
+        assert!((28..=52).contains(&z), "z {}", z);
+        assert_eq!(*chunk.get((x, y - 1, z)).unwrap(), Block::Stone);
+    }
+
+    #[test]
+    fn nowhere_to_spawn_in_a_chunk_of_liquid() {
+        assert_eq!(find_spawn(&ground(|_| Block::Water), (64, 64)), None);
+        assert_eq!(find_spawn(&ground(|x| if x < 64 { Block::Water } else { Block::Lava }), (0, 0)), None);
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::{BTreeMap, BTreeSet};
+use crate::{Block, HeightChunk, CHUNK, HEIGHT};
+use crate::falling::FallingBlocks;
+use crate::fluid;
+
+// Game ticks run at a fixed rate, however fast frames are drawn
+pub const TICKS_PER_SECOND: f64 = 20.;
+// Chunks are split into cubic sections, each of which gets the same number of random ticks
+pub const SECTION: usize = 16;
+pub const RANDOM_TICKS: usize = 3;
+
+type Pos = (usize, usize, usize);
+
+// What a scheduled tick does to its block
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Update {
+    // Checks whether the block has lost its support, see falling::FallingBlocks
+    Fall,
+    // Recomputes the liquid in the block from its neighbours, see fluid::step
+    Flow
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Tick {
+    Scheduled(Pos, Update),
+    Random(Pos)
+}
+
+pub struct Scheduler {
+    pub tick: u64,
+    rng: u64,
+    // Keyed by due tick, then by the order they were scheduled in
+    scheduled: BTreeMap<(u64, u64), (Pos, Update)>,
+    // Everything in scheduled, so a block waiting for an update isn't queued for it twice
+    queued: BTreeSet<(Pos, Update)>,
+    seq: u64
+}
+impl Scheduler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            tick: 0,
+            rng: seed | 1,
+            scheduled: BTreeMap::new(),
+            queued: BTreeSet::new(),
+            seq: 0
+        }
+    }
+
+    // xorshift64*, so random ticks repeat exactly for the same seed
+    pub fn random(&mut self) -> u64 {
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        return self.rng.wrapping_mul(0x2545F4914F6CDD1D)
+    }
+
+    // Runs update on c after delay ticks, unless it is already waiting for one
+    pub fn schedule(&mut self, c: Pos, update: Update, delay: u64) {
+        if self.queued.insert((c, update)) {
+            self.scheduled.insert((self.tick + delay.max(1), self.seq), (c, update));
+            self.seq += 1;
+        }
+    }
+
+    // Whether no block is waiting for update
+    pub fn is_settled(&self, update: Update) -> bool {
+        !self.queued.iter().any(|(_, u)| *u == update)
+    }
+
+    // Moves to the next tick and returns its block ticks in the order they should run:
+    // scheduled ticks by due tick and then queue order, then random ticks section by section
+    pub fn advance(&mut self) -> Vec<Tick> {
+        self.tick += 1;
+        let later = self.scheduled.split_off(&(self.tick + 1, 0));
+        let due = std::mem::replace(&mut self.scheduled, later);
+        let mut ticks = Vec::new();
+        for (c, update) in due.into_values() {
+            self.queued.remove(&(c, update));
+            ticks.push(Tick::Scheduled(c, update));
+        }
+
+        for sx in (0..CHUNK).step_by(SECTION) {
+            for sy in (0..HEIGHT).step_by(SECTION) {
+                for sz in (0..CHUNK).step_by(SECTION) {
+                    for _ in 0..RANDOM_TICKS {
+                        let r = self.random() as usize;
+                        let c = (sx + r % SECTION, sy + (r >> 8) % SECTION, sz + (r >> 16) % SECTION);
+                        if c.0 < CHUNK && c.1 < HEIGHT && c.2 < CHUNK {
+                            ticks.push(Tick::Random(c));
+                        }
+                    }
+                }
+            }
+        }
+        return ticks
+    }
+}
+
+// Runs the next tick of block updates on chunk. Returns whether any block changed.
+pub fn run(chunk: &mut HeightChunk, ticks: &mut Scheduler, falling: &mut FallingBlocks) -> bool {
+    let mut changed = false;
+    let mut flows = Vec::new();
+    for t in ticks.advance() {
+        match t {
+            Tick::Scheduled(c, Update::Fall) => falling.notify(c),
+            Tick::Scheduled(c, Update::Flow) => flows.push(c),
+            Tick::Random(c) => {
+                let r = ticks.random();
+                changed |= random_tick(chunk, c, r);
+            }
+        }
+    }
+    changed |= fluid::step(chunk, &flows, ticks);
+    return changed
+}
+
+const SPREAD: [(i64, i64, i64); 12] = [
+    (1, 0, 0), (-1, 0, 0), (0, 0, 1), (0, 0, -1),
+    (1, -1, 0), (-1, -1, 0), (0, -1, 1), (0, -1, -1),
+    (1, 1, 0), (-1, 1, 0), (0, 1, 1), (0, 1, -1)
+];
+
+// Grass spreads onto a nearby dirt block (picked by r) that has air above it, and dies back
+// to dirt when covered. Returns whether the chunk changed.
+pub fn random_tick(chunk: &mut HeightChunk, c: Pos, r: u64) -> bool {
+    if *chunk.get(c).unwrap_or(&Block::Air) != Block::Grass {
+        return false
+    }
+    let covered = |chunk: &HeightChunk, c: Pos| !chunk.is_empty_raw((c.0, c.1 + 1, c.2));
+    if covered(chunk, c) {
+        chunk.set(c, Block::Dirt, 0);
+        return true
+    }
+
+    let (dx, dy, dz) = SPREAD[(r % SPREAD.len() as u64) as usize];
+    let n = (c.0 as i64 + dx, c.1 as i64 + dy, c.2 as i64 + dz);
+    if n.0 < 0 || n.1 < 0 || n.2 < 0 {
+        return false
+    }
+    let n = (n.0 as usize, n.1 as usize, n.2 as usize);
+    if chunk.get(n) != Some(&Block::Dirt) || covered(chunk, n) {
+        return false
+    }
+    chunk.set(n, Block::Grass, 0);
+    return true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scheduled(ticks: &[Tick]) -> Vec<(Pos, Update)> {
+        ticks.iter().filter_map(|t| match t {
+            Tick::Scheduled(c, u) => Some((*c, *u)),
+            Tick::Random(_) => None
+        }).collect()
+    }
+
+    #[test]
+    fn scheduled_ticks_run_by_due_tick_then_in_order() {
+        let mut ticks = Scheduler::new(0);
+        ticks.schedule((1, 1, 1), Update::Fall, 2);
+        ticks.schedule((2, 2, 2), Update::Flow, 1);
+        ticks.schedule((3, 3, 3), Update::Flow, 2);
+        ticks.schedule((0, 0, 0), Update::Fall, 2);
+        assert_eq!(scheduled(&ticks.advance()), vec![((2, 2, 2), Update::Flow)]);
+        assert_eq!(scheduled(&ticks.advance()), vec![((1, 1, 1), Update::Fall), ((3, 3, 3), Update::Flow), ((0, 0, 0), Update::Fall)]);
+        assert!(scheduled(&ticks.advance()).is_empty());
+    }
+
+    #[test]
+    fn waiting_blocks_are_not_queued_twice() {
+        let mut ticks = Scheduler::new(0);
+        ticks.schedule((1, 1, 1), Update::Flow, 1);
+        ticks.schedule((1, 1, 1), Update::Flow, 1);
+        ticks.schedule((1, 1, 1), Update::Fall, 1);
+        assert!(!ticks.is_settled(Update::Flow));
+        assert_eq!(scheduled(&ticks.advance()), vec![((1, 1, 1), Update::Flow), ((1, 1, 1), Update::Fall)]);
+        assert!(ticks.is_settled(Update::Flow) && ticks.is_settled(Update::Fall));
+    }
+
+    #[test]
+    fn random_ticks_repeat_for_a_seed() {
+        let run = |seed: u64| {
+            let mut ticks = Scheduler::new(seed);
+            let mut all = Vec::new();
+            for _ in 0..5 {
+                all.extend(ticks.advance());
+            }
+            return all
+        };
+        assert_eq!(run(7), run(7));
+        assert_ne!(run(7), run(8));
+    }
+}
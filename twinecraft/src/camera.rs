@@ -1,8 +1,10 @@
 use winit::{
     event::*
 };
-use cgmath::Vector3;
-use chunk::HeightChunk;
+use cgmath::{Point3, Vector3};
+use chunk::{Block, HeightChunk};
+use chunk::physics::{self, Body};
+use crate::movement::MoveMode;
 use winit_input_helper::WinitInputHelper;
 
 #[rustfmt::skip]
@@ -47,28 +49,90 @@ pub struct CameraUniform {
 pub struct CameraController {
     xrot: f32,
     yrot: f32,
-    yvel: f32,
+    pub body: Body,
+    pub mode: MoveMode,
+    // Only walking is allowed without this, see GameMode::can_fly
+    pub can_fly: bool,
+    // Where the eye was before the last tick, for drawing in between ticks
+    prev: Point3<f32>,
+    // The camera lags behind the body after stepping up a ledge and catches up over a few ticks
+    ease: f32,
+    // Eye height above the feet, which sinks while crouching
+    eye_height: f32,
+    pub crouching: bool,
+    pub sprinting: bool,
+    // The liquid the player is swimming in
+    pub swimming: Option<Block>,
+    pub climbing: bool,
+    // Extra field of view while sprinting, in degrees
+    fov_boost: f32,
+    // Key presses seen in frames since the last tick
+    jump: bool,
+    cycle_mode: bool,
+    // Seconds since Space was last pressed, to spot double taps
+    since_jump: f32,
 }
 
 const EYE_OFFSET: Vector3<f32> = Vector3::new(0., -4., 0.);
 const SC: f32 = 1.;
+// Two presses of Space closer together than this switch between walking and flying
+const DOUBLE_TAP: f32 = 0.3;
+// How fast the camera catches up after a step, in blocks per second
+const STEP_EASE: f32 = 5.;
+// How fast the eye moves when crouching or standing up, in blocks per second
+const CROUCH_EASE: f32 = 3.;
+pub const FOV: f32 = 45.;
+const SPRINT_SPEED: f32 = 1.5;
+const SPRINT_FOV: f32 = 10.;
+// How fast the field of view widens and narrows, in degrees per second
+const FOV_EASE: f32 = 60.;
+const CROUCH_SPEED: f32 = 0.3;
+
+// Horizontal speed in a liquid, as a fraction of walking speed
+fn swim_speed(liquid: Block) -> f32 {
+    if liquid == Block::Lava { 0.3 } else { 0.5 }
+}
 
 type K = VirtualKeyCode;
 
 impl CameraController {
-    pub fn new() -> Self {
+    pub fn new(eye: Point3<f32>) -> Self {
+        let feet = eye - Vector3::new(0., physics::EYE_HEIGHT, 0.);
         Self {
             xrot: 0.,
             yrot: 0.,
-            yvel: 0.,
+            body: Body::player(feet),
+            prev: eye,
+            ease: 0.,
+            eye_height: physics::EYE_HEIGHT,
+            crouching: false,
+            sprinting: false,
+            swimming: None,
+            climbing: false,
+            fov_boost: 0.,
+            mode: MoveMode::Walk,
+            can_fly: false,
+            jump: false,
+            cycle_mode: false,
+            since_jump: DOUBLE_TAP,
         }
     }
 
-    pub fn update(&mut self, camera: &mut Camera, chunk: &HeightChunk, input: &WinitInputHelper, mouse_motion: (f32, f32), delta: f32) {
-        const PI: f32 = 3.14159;
+    // Moves the player so the eye is at eye, looking the same way as before
+    pub fn teleport(&mut self, eye: Point3<f32>) {
+        let feet = eye - Vector3::new(0., physics::EYE_HEIGHT, 0.);
+        let step_height = self.body.step_height;
+        self.body = Body::player(feet);
+        self.body.step_height = step_height;
+        self.prev = eye;
+        self.ease = 0.;
+        self.eye_height = physics::EYE_HEIGHT;
+        self.crouching = false;
+    }
 
-        let ef_fr = 10. * delta;
-        let ef_si = 10. * delta;
+    // Runs every frame: turns the view and remembers presses for the next tick
+    pub fn look(&mut self, input: &WinitInputHelper, mouse_motion: (f32, f32)) {
+        const PI: f32 = 3.14159;
 
         self.xrot += mouse_motion.0 * 10.;
         self.yrot += -mouse_motion.1 * 10.;
@@ -78,46 +142,116 @@ impl CameraController {
 
         self.yrot = self.yrot.clamp(-PI / 2., PI / 2.);
 
-        // Update camera
-        use cgmath::InnerSpace;
-        let forward = camera.target - camera.eye;
-        let forward_norm = forward.normalize();
-        let move_norm = Vector3::<f32>::new(forward_norm.x, 0., forward_norm.z);
-        let forward_mag = forward.magnitude();
+        self.jump |= input.key_pressed(K::Space);
+        // Every mode but walking flies, so there is nothing to switch to without flight
+        self.cycle_mode |= self.can_fly && input.key_pressed(K::M);
+    }
 
-        let feet = camera.eye + Vector3::new(0.,-1.,0.);
+    // Returns whether the mode is allowed
+    pub fn set_mode(&mut self, mode: MoveMode) -> bool {
+        if mode.flies() && !self.can_fly {
+            return false
+        }
+        self.mode = mode;
+        self.body.vel = Vector3::new(0., 0., 0.);
+        return true
+    }
 
-        if input.key_held(K::W) && chunk.is_empty(feet + move_norm * ef_fr) {
-            camera.eye += move_norm * ef_fr;
+    // Runs once per simulation tick of delta seconds
+    // Without controls, as while a screen is open, held keys are ignored but the body still moves
+    pub fn update(&mut self, chunk: &HeightChunk, input: &WinitInputHelper, controls: bool, delta: f32) {
+        use cgmath::InnerSpace;
+        let held = |key: K| controls && input.key_held(key);
+        // Before anything moves the eye, so easing into a crouch is interpolated too
+        self.prev = self.eye();
+        if std::mem::take(&mut self.cycle_mode) {
+            self.set_mode(self.mode.next());
         }
-        if input.key_held(K::S) && chunk.is_empty(feet - move_norm * ef_fr) {
-            camera.eye -= move_norm * ef_fr;
+        let jumped = std::mem::take(&mut self.jump);
+        self.since_jump += delta;
+        if jumped {
+            if self.since_jump < DOUBLE_TAP && matches!(self.mode, MoveMode::Walk | MoveMode::Fly) {
+                self.set_mode(if self.mode == MoveMode::Walk { MoveMode::Fly } else { MoveMode::Walk });
+            }
+            self.since_jump = 0.;
         }
-        let right = move_norm.cross(camera.up);
-        if input.key_held(K::D) && chunk.is_empty(feet + right * ef_si) {
-            camera.eye += right * ef_si;
+
+        let move_norm = Vector3::new(self.xrot.cos(), 0., self.xrot.sin());
+        let right = move_norm.cross(Vector3::unit_y());
+        let mut wish = Vector3::new(0., 0., 0.);
+        if held(K::W) { wish += move_norm }
+        if held(K::S) { wish -= move_norm }
+        if held(K::D) { wish += right }
+        if held(K::A) { wish -= right }
+        if wish.magnitude2() > 0. {
+            wish = wish.normalize();
         }
-        if input.key_held(K::A) && chunk.is_empty(feet - right * ef_si) {
-            camera.eye -= right * ef_si;
+        // LShift crouches when walking and sinks when flying. Standing back up needs headroom.
+        let crouch = self.mode == MoveMode::Walk && held(K::LShift);
+        let height = if crouch { physics::CROUCH_HEIGHT } else { physics::PLAYER_HEIGHT };
+        self.crouching = if self.body.resize(chunk, height) { crouch } else { true };
+        self.swimming = if self.mode == MoveMode::Walk { self.body.liquid(chunk) } else { None };
+        self.climbing = self.mode == MoveMode::Walk && self.swimming.is_none() && self.body.climbing(chunk);
+        self.body.sneaking = self.crouching && self.swimming.is_none();
+        self.sprinting = held(K::LControl) && held(K::W) && !self.crouching;
+
+        let mut speed = self.mode.speed();
+        if self.crouching { speed *= CROUCH_SPEED }
+        if self.sprinting { speed *= SPRINT_SPEED }
+        if let Some(liquid) = self.swimming { speed *= swim_speed(liquid) }
+        self.body.vel.x = wish.x * speed;
+        self.body.vel.z = wish.z * speed;
+
+        let eye_height = if self.crouching { physics::CROUCH_EYE_HEIGHT } else { physics::EYE_HEIGHT };
+        self.eye_height = approach(self.eye_height, eye_height, CROUCH_EASE * delta);
+        let boost = if self.sprinting { SPRINT_FOV } else { 0. };
+        self.fov_boost = approach(self.fov_boost, boost, FOV_EASE * delta);
+
+        if self.mode.flies() {
+            let mut up = 0.;
+            if held(K::Space) { up += 1. }
+            if held(K::LShift) { up -= 1. }
+            self.body.vel.y = up * self.mode.vertical_speed();
+        } else if let Some(liquid) = self.swimming {
+            self.body.swim(delta, liquid, held(K::Space));
+        } else if self.climbing {
+            // Forward or jump climbs, crouching holds on
+            self.body.climb(delta, held(K::W) || held(K::Space), self.crouching);
+        } else {
+            if jumped && self.body.on_ground {
+                self.body.vel.y = physics::JUMP;
+            }
+            self.body.apply_gravity(delta);
         }
-        // if input.key_held(K::Space) {
-        //     camera.eye += camera.up * ef_up;
-        // }
-        // if input.key_held(K::LShift) {
-        //     camera.eye -= camera.up * ef_up;
-        // }
-        if ! chunk.is_empty(camera.eye + Vector3::new(0.,-2.,0.)) {
-            self.yvel = self.yvel.max(0.);
+        if self.mode.collides() {
+            self.body.step(chunk, delta);
         } else {
-            self.yvel -= 0.05;
-            if self.yvel < -1. { self.yvel = -1. }
+            self.body.pos += self.body.vel * delta;
+            self.body.on_ground = false;
+            self.body.stepped = 0.;
         }
-
-        if input.key_pressed(K::Space) {
-            self.yvel += 0.5;
+        // Liquids break falls, and flying never lands hard
+        if self.swimming.is_some() || self.mode.flies() {
+            self.body.impact = 0.;
         }
-        camera.eye += camera.up * self.yvel;
+        self.ease = (self.ease - self.body.stepped + STEP_EASE * delta).min(0.);
+    }
 
-        camera.target = camera.eye + Vector3::new(self.xrot.cos(), self.yrot.sin(), self.xrot.sin()).normalize() * forward_mag;
+    // Where the eye is, which lags behind the body while easing after a step
+    fn eye(&self) -> Point3<f32> {
+        self.body.pos + Vector3::new(0., self.ease + self.eye_height, 0.)
     }
+
+    // Places the camera alpha of the way from the previous tick's position to the current one
+    pub fn apply(&self, camera: &mut Camera, alpha: f32) {
+        use cgmath::InnerSpace;
+        camera.eye = self.prev + (self.eye() - self.prev) * alpha;
+        camera.fovy = FOV + self.fov_boost;
+        camera.target = camera.eye + Vector3::new(self.xrot.cos(), self.yrot.sin(), self.xrot.sin()).normalize();
+    }
+}
+
+// Moves value towards target by at most step
+fn approach(value: f32, target: f32, step: f32) -> f32 {
+    if value < target { (value + step).min(target) } else { (value - step).max(target) }
 }
@@ -0,0 +1,25 @@
+use std::io::BufRead;
+use std::sync::mpsc::{channel, Receiver};
+
+// Commands are typed into the terminal the game was started from, one per line
+pub struct Console {
+    rx: Receiver<String>
+}
+impl Console {
+    pub fn spawn() -> Self {
+        let (tx, rx) = channel();
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lock().lines() {
+                match line {
+                    Ok(line) => if tx.send(line).is_err() { break },
+                    Err(_) => break
+                }
+            }
+        });
+        return Console { rx }
+    }
+
+    pub fn poll(&self) -> Vec<String> {
+        self.rx.try_iter().collect()
+    }
+}
@@ -0,0 +1,46 @@
+use chunk::breaking::STAGES;
+
+// Width and height of one stage of the crack overlay
+pub const SIZE: u32 = 16;
+const ARMS: usize = 6;
+const ARM_LENGTH: usize = 12;
+
+// Crack overlays for every breaking stage, stacked top to bottom in one RGBA image. Cracks
+// wander out from the middle, and each stage draws more of the same cracks than the last.
+pub fn atlas() -> Vec<u8> {
+    let mut rng: u32 = 0x9E37_79B9;
+    let mut next = move || {
+        rng ^= rng << 13;
+        rng ^= rng >> 17;
+        rng ^= rng << 5;
+        rng
+    };
+
+    // Pixels in the order they crack
+    let mut order = Vec::new();
+    let dirs = [(1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1)];
+    for arm in 0..ARMS {
+        let (dx, dy) = dirs[(arm * dirs.len() / ARMS + next() as usize % 2) % dirs.len()];
+        let (mut x, mut y) = (SIZE as i32 / 2, SIZE as i32 / 2);
+        for _ in 0..ARM_LENGTH {
+            order.push((x as u32, y as u32));
+            // Mostly outwards, sometimes off to a side
+            match next() % 3 {
+                0 => { x += dy; y += dx; },
+                _ => { x += dx; y += dy; }
+            }
+            x = x.clamp(0, SIZE as i32 - 1);
+            y = y.clamp(0, SIZE as i32 - 1);
+        }
+    }
+
+    let mut rgba = vec![0; (SIZE * SIZE * STAGES as u32 * 4) as usize];
+    for stage in 0..STAGES as u32 {
+        let shown = order.len() * (stage as usize + 1) / STAGES as usize;
+        for (x, y) in &order[..shown] {
+            let i = (((stage * SIZE + y) * SIZE + x) * 4) as usize;
+            rgba[i..i + 4].copy_from_slice(&[20, 15, 10, 220]);
+        }
+    }
+    return rgba
+}
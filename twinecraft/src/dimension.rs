@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use wgpu::Color;
+use cgmath::Point3;
+use chunk::{Block, HeightChunk, HEIGHT};
+use chunk::gen::{Caverns, Flat, Generator};
+use chunk::save;
+use chunk::falling::FallingBlocks;
+use chunk::entity::Entities;
+use chunk::fluid;
+use chunk::tick::{self, Scheduler, Update};
+use chunk::physics;
+use chunk::player::Player;
+use chunk::item::ItemStack;
+
+// Column of each dimension that holds its portal
+pub const PORTAL: (usize, usize) = (8, 8);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DimensionId {
+    Overworld,
+    Caverns,
+    Flat
+}
+impl DimensionId {
+    pub const ALL: [DimensionId; 3] = [DimensionId::Overworld, DimensionId::Caverns, DimensionId::Flat];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DimensionId::Overworld => "overworld",
+            DimensionId::Caverns => "caverns",
+            DimensionId::Flat => "flat"
+        }
+    }
+    pub fn from_name(name: &str) -> Option<DimensionId> {
+        Self::ALL.iter().copied().find(|d| d.name() == name)
+    }
+
+    // Portals lead through the dimensions in order and wrap around
+    pub fn portal_target(self) -> DimensionId {
+        let i = Self::ALL.iter().position(|d| *d == self).unwrap();
+        return Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+
+    fn sky(self) -> Color {
+        match self {
+            DimensionId::Overworld => Color { r: 0.1, g: 0.2, b: 0.3, a: 1.0 },
+            DimensionId::Caverns => Color { r: 0.02, g: 0.02, b: 0.03, a: 1.0 },
+            DimensionId::Flat => Color { r: 0.5, g: 0.7, b: 0.9, a: 1.0 }
+        }
+    }
+}
+
+pub struct Dimension {
+    pub height: HeightChunk,
+    pub falling: FallingBlocks,
+    pub entities: Entities,
+    pub ticks: Scheduler,
+    pub sky: Color,
+    // Floor level of the portal, where travellers arrive
+    pub portal_y: usize
+}
+impl Dimension {
+    fn find_portal(height: &HeightChunk) -> Option<usize> {
+        (0..HEIGHT).find(|y| *height.get((PORTAL.0, *y, PORTAL.1)).unwrap() == Block::Portal)
+    }
+
+    // Clears a small room around the portal column, so it can be reached and left in
+    // any terrain, including the solid parts of the caverns
+    fn place_portal(height: &mut HeightChunk) -> usize {
+        let standable = |y: usize| !height.is_empty_raw((PORTAL.0, y - 1, PORTAL.1))
+            && (y..y + 3).all(|y| height.is_empty_raw((PORTAL.0, y, PORTAL.1)));
+        let y = (1..HEIGHT - 3).find(|y| standable(*y)).unwrap_or(HEIGHT / 2);
+
+        for x in PORTAL.0 - 2..=PORTAL.0 + 3 {
+            for z in PORTAL.1 - 2..=PORTAL.1 + 3 {
+                if height.is_empty_raw((x, y - 1, z)) {
+                    height.set((x, y - 1, z), Block::Stone, 0);
+                }
+                for y in y..y + 3 {
+                    height.set((x, y, z), Block::Air, 0);
+                }
+            }
+        }
+        height.set((PORTAL.0, y, PORTAL.1), Block::Portal, 0);
+        return y
+    }
+
+    // Runs one game tick of block updates. Returns whether any block changed.
+    pub fn tick(&mut self) -> bool {
+        return tick::run(&mut self.height, &mut self.ticks, &mut self.falling)
+    }
+
+    // Lands whatever is still falling, so the chunk is whole while the dimension isn't
+    // updated. Dropped items and scheduled ticks stay with the dimension until it is
+    // entered again.
+    pub fn settle(&mut self) {
+        for c in self.falling.land_all(&mut self.height) {
+            fluid::notify(&mut self.ticks, &self.height, c);
+        }
+        self.drop_blocked(0);
+    }
+
+    // Falling blocks that came down on a block they can't replace are dropped there as items
+    pub fn drop_blocked(&mut self, r: u64) {
+        for (i, (c, block)) in self.falling.blocked.drain(..).enumerate() {
+            if let Some(item) = block.drop() {
+                self.entities.drop_item(c, ItemStack::new(item, 1), r + i as u64 * 90);
+            }
+        }
+    }
+
+    // Call after changing block c, so the blocks and liquids around it react
+    pub fn changed(&mut self, c: (usize, usize, usize)) {
+        fluid::notify(&mut self.ticks, &self.height, c);
+        self.ticks.schedule(c, Update::Fall, 2);
+    }
+
+    // Next to the portal rather than in it, so arriving doesn't send the player straight back
+    pub fn arrival(&self) -> Point3<f32> {
+        // Standing on the floor of the portal room, which is the top of the block below portal_y
+        Point3::new(PORTAL.0 as f32 + 2., self.portal_y as f32 - 0.5 + physics::EYE_HEIGHT, PORTAL.1 as f32 + 2.)
+    }
+}
+
+// Names the file an error came from
+fn in_file(path: &Path, e: io::Error) -> io::Error {
+    io::Error::new(e.kind(), format!("{}: {}", path.display(), e))
+}
+
+// Every dimension has its own chunk storage and generator. Dimensions are loaded or
+// generated the first time they are entered.
+pub struct World {
+    pub current: DimensionId,
+    dims: HashMap<DimensionId, Dimension>,
+    generators: HashMap<DimensionId, Box<dyn Generator>>,
+    pub level: save::Level,
+    pub player: Player,
+    dir: Option<PathBuf>
+}
+impl World {
+    // A saved world keeps the seed it was created with, so seed only matters for new worlds.
+    // overworld makes the overworld's generator from the world's seed.
+    pub fn new(dir: Option<PathBuf>, seed: u32, overworld: impl FnOnce(u32) -> Box<dyn Generator>) -> io::Result<Self> {
+        let mut level = match &dir {
+            Some(dir) => save::read_level(dir).map_err(|e| in_file(&dir.join("level.txt"), e))?,
+            None => save::Level::default()
+        };
+        let player = match &dir {
+            Some(dir) => save::read_player(dir).map_err(|e| in_file(&dir.join("player.txt"), e))?,
+            None => Player::new()
+        };
+        let seed = *level.seed.get_or_insert(seed);
+
+        let mut generators = HashMap::<DimensionId, Box<dyn Generator>>::new();
+        generators.insert(DimensionId::Overworld, overworld(seed));
+        generators.insert(DimensionId::Caverns, Box::new(Caverns::new(seed)));
+        generators.insert(DimensionId::Flat, Box::new(Flat {
+            layers: vec![Block::Stone, Block::Dirt, Block::Dirt, Block::Grass]
+        }));
+
+        let mut world = Self {
+            current: DimensionId::Overworld,
+            dims: HashMap::new(),
+            generators,
+            level,
+            player,
+            dir
+        };
+        world.load(DimensionId::Overworld)?;
+        return Ok(world)
+    }
+
+    // The overworld saves to the root of the world directory, so worlds saved before
+    // dimensions existed still load
+    fn dir_of(&self, id: DimensionId) -> Option<PathBuf> {
+        match id {
+            DimensionId::Overworld => self.dir.clone(),
+            _ => self.dir.as_ref().map(|dir| dir.join(id.name()))
+        }
+    }
+
+    // A dimension whose save can't be read stays unloaded, so it isn't saved over
+    fn load(&mut self, id: DimensionId) -> io::Result<()> {
+        if self.dims.contains_key(&id) {
+            return Ok(())
+        }
+        let saved = match self.dir_of(id) {
+            Some(dir) => save::read_chunk(&dir, 0, 0).map_err(|e| in_file(&save::chunk_path(&dir, 0, 0), e))?,
+            None => None
+        };
+        let mut height = saved.unwrap_or_else(|| self.generators[&id].generate(0, 0));
+        let portal_y = match Dimension::find_portal(&height) {
+            Some(y) => y,
+            None => Dimension::place_portal(&mut height)
+        };
+        self.dims.insert(id, Dimension { height, falling: FallingBlocks::new(), entities: Entities::new(), ticks: Scheduler::new(0), sky: id.sky(), portal_y });
+        return Ok(())
+    }
+
+    pub fn switch(&mut self, id: DimensionId) -> io::Result<&Dimension> {
+        self.load(id)?;
+        if id != self.current {
+            self.current_mut().settle();
+        }
+        self.current = id;
+        return Ok(self.current())
+    }
+
+    pub fn current(&self) -> &Dimension {
+        &self.dims[&self.current]
+    }
+    pub fn current_mut(&mut self) -> &mut Dimension {
+        self.dims.get_mut(&self.current).unwrap()
+    }
+
+    // Moves items lying within reach of body into the player's inventory
+    pub fn pick_up(&mut self, body: &physics::Body) {
+        let inventory = &mut self.player.inventory;
+        self.dims.get_mut(&self.current).unwrap().entities.pick_up(body, |stack| inventory.add(stack));
+    }
+
+    pub fn save(&mut self) {
+        for dim in self.dims.values_mut() {
+            dim.settle();
+        }
+        if let Some(dir) = &self.dir {
+            if let Err(e) = save::write_level(dir, &self.level) {
+                eprintln!("could not save level: {}", e);
+            }
+            if let Err(e) = save::write_player(dir, &self.player) {
+                eprintln!("could not save player: {}", e);
+            }
+        }
+        for (id, dim) in self.dims.iter() {
+            if let Some(dir) = self.dir_of(*id) {
+                if let Err(e) = save::write_chunk(&dir, 0, 0, &dim.height) {
+                    eprintln!("could not save {}: {}", id.name(), e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chunk::item::Item;
+
+    fn world() -> World {
+        World::new(None, 0, |_| Box::new(Flat { layers: vec![Block::Stone, Block::Dirt, Block::Grass] })).unwrap()
+    }
+
+    #[test]
+    fn portals_lead_through_every_dimension_and_back() {
+        let mut id = DimensionId::Overworld;
+        let mut visited = vec![id];
+        for _ in 1..DimensionId::ALL.len() {
+            id = id.portal_target();
+            assert!(!visited.contains(&id), "{} visited twice", id.name());
+            visited.push(id);
+        }
+        assert_eq!(id.portal_target(), DimensionId::Overworld);
+    }
+
+    #[test]
+    fn every_dimension_has_a_portal_to_arrive_next_to() {
+        let mut world = world();
+        for id in DimensionId::ALL {
+            let dim = world.switch(id).unwrap();
+            assert_eq!(Dimension::find_portal(&dim.height), Some(dim.portal_y), "in {}", id.name());
+            let feet = HeightChunk::to_index(dim.arrival() - cgmath::Vector3::new(0., physics::EYE_HEIGHT - 0.5, 0.));
+            assert_eq!(*dim.height.get(feet).unwrap(), Block::Air, "in {}", id.name());
+            assert!(dim.height.get((feet.0, feet.1 - 1, feet.2)).unwrap().is_solid(), "in {}", id.name());
+            assert_ne!(dim.height.getp(dim.arrival()), Some(&Block::Portal), "in {}", id.name());
+        }
+    }
+
+    #[test]
+    fn switching_keeps_items_and_ticks_and_lands_falling_blocks() {
+        let mut world = world();
+        let dim = world.current_mut();
+        let c = (3, dim.portal_y + 2, 3);
+        dim.height.set(c, Block::Sand, 0);
+        dim.falling.notify(c);
+        dim.ticks.schedule(c, Update::Flow, 5);
+        dim.entities.drop_item((5, dim.portal_y, 5), ItemStack::new(Item::Block(Block::Dirt), 1), 0);
+        dim.falling.update(&mut dim.height, 1. / 60.);
+        assert_eq!(dim.falling.entities.len(), 1);
+
+        world.switch(DimensionId::Caverns).unwrap();
+        let dim = world.switch(DimensionId::Overworld).unwrap();
+        assert!(dim.falling.entities.is_empty());
+        assert_eq!(*dim.height.get((3, dim.portal_y, 3)).unwrap(), Block::Sand);
+        assert!(!dim.entities.is_empty());
+        assert!(!dim.ticks.is_settled(Update::Flow));
+    }
+}
@@ -4,13 +4,27 @@ use winit::{
     event_loop::{ControlFlow, EventLoop},
     window::{Window, WindowBuilder},
 };
-use noise::NoiseFn;
 use wgpu::*;
-use chunk::HeightChunk;
+use chunk::{Block, HeightChunk};
+use chunk::gen::{Generator, Terrain};
+use chunk::mesh::FaceInstance;
+use chunk::heightmap::Heightmap;
 use cgmath::InnerSpace;
 use winit_input_helper::WinitInputHelper;
 
 use std::time::{SystemTime};
+use std::path::Path;
+use cgmath::Vector3;
+use chunk::physics;
+use chunk::health::{Damage, Health};
+use chunk::spawn;
+use chunk::fluid;
+use chunk::tick;
+use chunk::player::GameMode;
+use chunk::item::{Item, ItemStack};
+use chunk::inventory::HOTBAR;
+use chunk::breaking::{self, Breaking, Tool};
+use chunk::crafting::RecipeBook;
 mod setup;
 use setup::Ctx;
 
@@ -23,11 +37,25 @@ use camera::{CameraController, Camera};
 mod texture;
 use texture::Texture;
 
+mod dimension;
+use dimension::{DimensionId, World};
+
+mod console;
+use console::Console;
+
+mod movement;
+use movement::MoveMode;
+
+mod crack;
+
+mod ui;
+use ui::{CraftingScreen, UiVertex};
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 struct Vertex {
-    position: [f32; 3],
-    color: [f32; 3]
+    // Corner of the face quad, the shader turns it into a position from the face index
+    corner: [f32; 2]
 }
 impl Vertex {
     fn desc<'a>() -> VertexBufferLayout<'a> {
@@ -38,12 +66,7 @@ impl Vertex {
             VertexAttribute {
                 offset: 0,
                 shader_location: 0,
-                format: VertexFormat::Float32x3,
-            },
-            VertexAttribute {
-                offset: std::mem::size_of::<[f32; 3]>() as BufferAddress,
-                shader_location: 1,
-                format: VertexFormat::Float32x3,
+                format: VertexFormat::Float32x2,
             }
             ]
         }
@@ -51,36 +74,69 @@ impl Vertex {
 }
 
 const VERTICES: &[Vertex] = &[
-Vertex { position: [0.5, 0.5, 0.0], color: [1.,1.,1.] }, // 0
-Vertex { position: [0.5, -0.5, 0.0], color: [0.,0.,0.] }, // 1
-Vertex { position: [-0.5, -0.5, 0.0], color: [0.,0.,0.] }, // 2
-Vertex { position: [-0.5, 0.5, 0.0], color: [1.,1.,1.] }, // 3
-
-Vertex { position: [0.5, 0.5, -1.], color: [1.,1.,1.] }, // 4
-Vertex { position: [0.5, -0.5, -1.], color: [0.,0.,0.] }, // 5
-Vertex { position: [-0.5, -0.5, -1.], color: [0.,0.,0.] }, // 6
-Vertex { position: [-0.5, 0.5, -1.], color: [1.,1.,1.] }, // 7
+Vertex { corner: [0., 0.] }, // 0
+Vertex { corner: [1., 0.] }, // 1
+Vertex { corner: [1., 1.] }, // 2
+Vertex { corner: [0., 1.] }, // 3
 ];
 
 const INDICES: &[u16] = &[
 0,1,2,
-0,2,3,
-
-4,5,1,
-4,1,0,
+0,2,3
+];
 
-4,5,6,
-4,6,7,
+// The world is simulated in fixed steps, however fast frames are drawn, and every few
+// steps is also a game tick
+const STEPS_PER_TICK: u64 = 3;
+const STEP: f64 = 1. / (tick::TICKS_PER_SECOND * STEPS_PER_TICK as f64);
+// A frame that took longer than this (say, while the window was dragged) is cut short
+// rather than simulated in one long burst
+const MAX_FRAME: f64 = 0.25;
+// Keys 1 to 9 select the hotbar slots
+const HOTBAR_KEYS: [VirtualKeyCode; HOTBAR] = [
+    VirtualKeyCode::Key1, VirtualKeyCode::Key2, VirtualKeyCode::Key3,
+    VirtualKeyCode::Key4, VirtualKeyCode::Key5, VirtualKeyCode::Key6,
+    VirtualKeyCode::Key7, VirtualKeyCode::Key8, VirtualKeyCode::Key9
+];
 
-7,6,2,
-7,2,3,
+// Fog and tint over everything while the camera is inside a liquid
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct FogUniform {
+    // Fog color, and how thick the fog is in w. No fog at all when that is 0.
+    color: [f32; 4],
+    eye: [f32; 4]
+}
+impl FogUniform {
+    fn new(liquid: Option<Block>, eye: cgmath::Point3<f32>) -> Self {
+        let color = match liquid {
+            Some(Block::Water) => [0.1, 0.25, 0.55, 0.12],
+            Some(Block::Lava) => [0.8, 0.3, 0.05, 1.2],
+            _ => [0., 0., 0., 0.]
+        };
+        return Self { color, eye: [eye.x, eye.y, eye.z, 1.] }
+    }
 
-4,0,3,
-4,3,7,
+    fn clear_color(&self) -> Color {
+        Color { r: self.color[0] as f64, g: self.color[1] as f64, b: self.color[2] as f64, a: 1. }
+    }
+}
 
-5,1,2,
-5,2,6
-];
+// The block being broken and how far along it is, for drawing cracks over it
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct CrackUniform {
+    // Block position, and the crack stage in w, or -1 when nothing is being broken
+    target: [f32; 4]
+}
+impl CrackUniform {
+    fn new(breaking: &Breaking) -> Self {
+        match breaking.stage() {
+            Some((c, stage)) => Self { target: [c.0 as f32, c.1 as f32, c.2 as f32, stage as f32] },
+            None => Self { target: [0., 0., 0., -1.] }
+        }
+    }
+}
 
 struct Model {
     vert_buffer: Buffer,
@@ -91,29 +147,56 @@ struct Model {
 
 struct State {
     ctx: Ctx,
-    clear_color: Color,
     depth_texture: Texture,
     
-    height: HeightChunk,
+    world: World,
     height_changed: bool,
+    console: Console,
     
     block_render_pipe: RenderPipeline,
     block_model: Model,
     blocks_buffer: Buffer,
+    blocks_count: u32,
+    // Falling blocks and dropped items move every tick, so they are kept out of the chunk's faces
+    entities_buffer: Buffer,
+    entities_count: u32,
     
     time_buffer: Buffer,
     time_bind_group: BindGroup,
     secs: f64,
+    // Which of the fixed steps of a game tick comes next, the tick running on the first
+    step: u64,
     
     cam: Camera,
     cam_control: CameraController,
+    health: Health,
     cam_buffer: Buffer,
     cam_bind_group: BindGroup,
+
+    fog: FogUniform,
+    fog_buffer: Buffer,
+    fog_bind_group: BindGroup,
+
+    crack_buffer: Buffer,
+    crack_texture: Texture,
+    crack_bind_group: BindGroup,
+    breaking: Breaking,
+
+    recipes: RecipeBook,
+    // Open while the player is crafting, which frees the mouse from looking around
+    screen: Option<CraftingScreen>,
+    ui_pipe: RenderPipeline,
+    ui_buffer: Buffer,
+    ui_count: u32,
     
-    switch: bool
+    switch: bool,
+    // E was pressed in a frame since the last tick
+    break_pressed: bool,
+    // And Q, which places the held block
+    place_pressed: bool
 }
 impl State {
-    async fn new(window: &Window) -> State {
+    async fn new(window: &Window, mut world: World, spawn_near: Option<(usize, usize)>, recipes: RecipeBook) -> State {
         let ctx = Ctx::new(window).await;
         
         // Time buffer
@@ -172,7 +255,7 @@ impl State {
             // which way is "up"
             up: cgmath::Vector3::unit_y(),
             aspect: ctx.config.width as f32 / ctx.config.height as f32,
-            fovy: 45.0,
+            fovy: camera::FOV,
             znear: 0.1,
             zfar: 1000.0,
         };
@@ -206,23 +289,148 @@ impl State {
             label: Some("camera_bind_group"),
         });
         
-        // Positions
+        // Fog buffer
+        let fog = FogUniform::new(None, cam.eye);
+        let fog_buffer = fast_buffer(&ctx.device, &[fog], BufferUsages::UNIFORM | BufferUsages::COPY_DST);
+        let fog_bind_group_layout = ctx.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }
+            ],
+            label: Some("fog_bind_group_layout"),
+        });
+        let fog_bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &fog_bind_group_layout,
+            entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: fog_buffer.as_entire_binding(),
+            }
+            ],
+            label: Some("fog_bind_group"),
+        });
         
-        let ns = noise::SuperSimplex::new();
-        let height = HeightChunk::for_each(|x,y,z| {
-            let v = ns.get([x as f64 / 50.,z as f64 / 50.]) as f32 * 10. + 5.;
-            return (y as f32) < v;
+        // Crack overlay
+        let crack_buffer = fast_buffer(&ctx.device, &[CrackUniform::new(&Breaking::new())], BufferUsages::UNIFORM | BufferUsages::COPY_DST);
+        let crack_texture = Texture::from_rgba(&ctx.device, &ctx.queue, crack::SIZE, crack::SIZE * breaking::STAGES as u32, &crack::atlas(), "crack texture");
+        let crack_bind_group_layout = ctx.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler {
+                    filtering: true,
+                    comparison: false,
+                },
+                count: None,
+            }
+            ],
+            label: Some("crack_bind_group_layout"),
+        });
+        let crack_bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &crack_bind_group_layout,
+            entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: crack_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(&crack_texture.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::Sampler(&crack_texture.sampler),
+            }
+            ],
+            label: Some("crack_bind_group"),
         });
-        let pos_buffer = fast_buffer(&ctx.device, &height.positions(), BufferUsages::VERTEX | BufferUsages::COPY_DST);
         
+        // Positions
+        
+        // Worlds keep the spawn point they were first given, unless asked to look elsewhere
+        if world.level.spawn.is_none() || spawn_near.is_some() {
+            let near = spawn_near.unwrap_or((0, 0));
+            let found = spawn::find_spawn(&world.current().height, near);
+            world.level.spawn = Some(found.unwrap_or((near.0, chunk::HEIGHT, near.1)));
+        }
+        let spawn = eye_at(world.level.spawn.unwrap());
+        let instances = world.current().height.faces();
+        let pos_buffer = fast_buffer(&ctx.device, &instances, BufferUsages::VERTEX | BufferUsages::COPY_DST);
+        let entities_buffer = fast_buffer::<FaceInstance>(&ctx.device, &[], BufferUsages::VERTEX);
+        
+        // Matches the layout of chunk::mesh::FaceInstance
         let pos_desc = VertexBufferLayout {
-            array_stride: std::mem::size_of::<[f32; 3]>() as BufferAddress,
+            array_stride: std::mem::size_of::<FaceInstance>() as BufferAddress,
             step_mode: VertexStepMode::Instance,
             attributes: &[
             VertexAttribute {
                 offset: 0,
+                shader_location: 1,
+                format: VertexFormat::Float32x3
+            },
+            VertexAttribute {
+                offset: std::mem::size_of::<[f32; 3]>() as BufferAddress,
                 shader_location: 2,
+                format: VertexFormat::Float32
+            },
+            VertexAttribute {
+                offset: std::mem::size_of::<[f32; 4]>() as BufferAddress,
+                shader_location: 3,
                 format: VertexFormat::Float32x3
+            },
+            VertexAttribute {
+                offset: std::mem::size_of::<[f32; 7]>() as BufferAddress,
+                shader_location: 4,
+                format: VertexFormat::Float32
+            },
+            VertexAttribute {
+                offset: std::mem::size_of::<[f32; 8]>() as BufferAddress,
+                shader_location: 5,
+                format: VertexFormat::Float32x4
+            },
+            VertexAttribute {
+                offset: std::mem::size_of::<[f32; 12]>() as BufferAddress,
+                shader_location: 6,
+                format: VertexFormat::Float32x4
+            },
+            VertexAttribute {
+                offset: std::mem::size_of::<[f32; 16]>() as BufferAddress,
+                shader_location: 7,
+                format: VertexFormat::Float32
+            },
+            VertexAttribute {
+                offset: std::mem::size_of::<[f32; 17]>() as BufferAddress,
+                shader_location: 8,
+                format: VertexFormat::Float32
             }
             ]
         };
@@ -240,7 +448,9 @@ impl State {
             label: Some("Render Pipeline Layout"),
             bind_group_layouts: &[
             &time_bind_group_layout,
-            &cam_bind_group_layout
+            &cam_bind_group_layout,
+            &fog_bind_group_layout,
+            &crack_bind_group_layout
             ],
             push_constant_ranges: &[],
         });
@@ -284,21 +494,50 @@ impl State {
             multisample: MultisampleState::default()
         });
         
+        // Screens drawn over the world, without depth
+        let ui_shader = ctx.device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("UI Shader"),
+            source: ShaderSource::Wgsl(include_str!("ui.wgsl").into()),
+        });
+        let ui_pipeline_layout = ctx.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("UI Pipeline Layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+        let ui_pipe = ctx.device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("UI Pipeline"),
+            layout: Some(&ui_pipeline_layout),
+            vertex: VertexState {
+                module: &ui_shader,
+                entry_point: "vs_main",
+                buffers: &[UiVertex::desc()],
+            },
+            fragment: Some(FragmentState {
+                module: &ui_shader,
+                entry_point: "fs_main",
+                targets: &[ColorTargetState {
+                    format: ctx.config.format,
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                }],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default()
+        });
+        let ui_buffer = fast_buffer::<UiVertex>(&ctx.device, &[], BufferUsages::VERTEX);
+        
         // Vertex buffer stuff
         let vert_buffer = fast_buffer(&ctx.device, VERTICES, BufferUsages::VERTEX);
         let indx_buffer = fast_buffer(&ctx.device, INDICES, BufferUsages::INDEX);
         
-        Self {
+        let game_mode = world.player.game_mode;
+        let mut state = Self {
             ctx,
-            clear_color: Color {
-                r: 0.1,
-                g: 0.2,
-                b: 0.3,
-                a: 1.0,
-            },
             depth_texture,
-            height,
+            world,
             height_changed: false,
+            console: Console::spawn(),
             
             block_render_pipe: render_pipeline,
             block_model: Model {
@@ -308,56 +547,354 @@ impl State {
                 indxs: INDICES.len() as u32
             },
             blocks_buffer: pos_buffer,
+            blocks_count: instances.len() as u32,
+            entities_buffer,
+            entities_count: 0,
             
             time_buffer,
             time_bind_group,
             secs: 0.,
+            step: 0,
             
+            cam_control: CameraController::new(spawn),
+            health: Health::new(),
             cam,
-            cam_control: CameraController::new(),
             cam_buffer,
             cam_bind_group,
             
-            switch: false
-        }
+            fog,
+            fog_buffer,
+            fog_bind_group,
+            
+            crack_buffer,
+            crack_texture,
+            crack_bind_group,
+            breaking: Breaking::new(),
+
+            recipes,
+            screen: None,
+            ui_pipe,
+            ui_buffer,
+            ui_count: 0,
+            
+            switch: false,
+            break_pressed: false,
+            place_pressed: false
+        };
+        state.set_game_mode(game_mode);
+        return state
     }
     
-    fn update(&mut self, delta: f64, window: &Window, input: &WinitInputHelper, mouse_motion: (f64, f64)) {
+    // Runs every frame, before any ticks it needs
+    fn frame(&mut self, window: &Window, input: &WinitInputHelper, mouse_motion: (f64, f64)) {
         let rel_mouse_motion = (
             (mouse_motion.0 / window.inner_size().width as f64) as f32,
             (mouse_motion.1 / window.inner_size().width as f64) as f32
         );
+        if input.key_pressed(VirtualKeyCode::C) {
+            self.toggle_crafting(window);
+        }
+        let size = (self.ctx.size.width as f32, self.ctx.size.height as f32);
+        if let Some(screen) = &mut self.screen {
+            if let (true, Some(mouse)) = (input.mouse_pressed(0), input.mouse()) {
+                if let Some(slot) = CraftingScreen::slot_at(size, mouse) {
+                    screen.click(slot, &mut self.world.player.inventory, &self.recipes);
+                }
+            }
+            if let Some(verts) = screen.redraw(size, &self.world.player.inventory, &self.recipes, input.mouse()) {
+                self.ui_buffer = fast_buffer(&self.ctx.device, &verts, BufferUsages::VERTEX);
+                self.ui_count = verts.len() as u32;
+            }
+        } else {
+            self.cam_control.look(input, rel_mouse_motion);
+            self.break_pressed |= input.key_pressed(VirtualKeyCode::E);
+            self.place_pressed |= input.key_pressed(VirtualKeyCode::Q);
+        }
 
-        self.cam_control.update(
-            &mut self.cam, 
-            &self.height, 
-            input,
-            rel_mouse_motion,
-            delta as f32
-        );
-        
+        let inventory = &mut self.world.player.inventory;
+        for (i, key) in HOTBAR_KEYS.iter().enumerate() {
+            if input.key_pressed(*key) {
+                inventory.select(i);
+            }
+        }
+        // Scrolling down moves right along the hotbar
+        let scroll = input.scroll_diff();
+        if scroll != 0. {
+            inventory.scroll(-scroll.signum() as i32);
+        }
+    }
+
+    // C opens the crafting screen and closes it again, handing the mouse back to the camera
+    fn toggle_crafting(&mut self, window: &Window) {
+        let open = self.screen.is_none();
+        if open {
+            self.screen = Some(CraftingScreen::new());
+        } else {
+            self.close_crafting();
+        }
+        window.set_cursor_grab(!open).unwrap_or_else(|e| eprintln!("could not grab the cursor: {}", e));
+    }
+
+    // Whatever the inventory has no room for is dropped at the player's feet
+    fn close_crafting(&mut self) {
+        let screen = match self.screen.take() {
+            Some(screen) => screen,
+            None => return
+        };
+        let feet = self.cam_control.body.pos + Vector3::new(0., 0.5, 0.);
+        let c = HeightChunk::to_index(feet);
+        let r = (self.secs * 1000.) as u64;
+        for (i, stack) in screen.close(&mut self.world.player.inventory).into_iter().enumerate() {
+            self.world.current_mut().entities.drop_item(c, stack, r + i as u64 * 90);
+        }
+    }
+
+    // The tool in the selected hotbar slot, or the bare hand
+    fn held_tool(&self) -> Tool {
+        match self.world.player.inventory.held() {
+            Some(ItemStack { item: Item::Tool(tool), .. }) => tool,
+            _ => Tool::Hand
+        }
+    }
+
+    fn give(&mut self, stack: ItemStack) {
+        if let Some(left) = self.world.player.inventory.add(stack) {
+            println!("no room for {} {}", left.count, left.item.name());
+        }
+    }
+
+    // Runs after the frame's ticks; alpha is how far the frame is into the next tick
+    fn interpolate(&mut self, alpha: f32) {
+        self.cam_control.apply(&mut self.cam, alpha);
         self.ctx.queue.write_buffer(&self.cam_buffer, 0, bytemuck::cast_slice(&[self.cam.uniform()]));
+        let liquid = physics::liquid_at(&self.world.current().height, self.cam.eye);
+        self.fog = FogUniform::new(liquid, self.cam.eye);
+        self.ctx.queue.write_buffer(&self.fog_buffer, 0, bytemuck::cast_slice(&[self.fog]));
+        self.ctx.queue.write_buffer(&self.crack_buffer, 0, bytemuck::cast_slice(&[CrackUniform::new(&self.breaking)]));
+    }
 
-        type K = VirtualKeyCode;
-        if input.key_pressed(K::E) {
-            let target = self.height.ray(self.cam.eye, (self.cam.target - self.cam.eye).normalize() * 5.);
+    // One fixed simulation step of STEP seconds
+    fn update(&mut self, input: &WinitInputHelper) {
+        let delta = STEP;
+        self.cam_control.update(&self.world.current().height, input, self.screen.is_none(), delta as f32);
 
-            match target {
-                Some((ix, b)) => {
-                    *self.height.get_mut(ix).unwrap() = false;
-                    self.height_changed = true;
-                },
-                None => {}
-            };
+        // Only walking players get hurt, and only in survival
+        if self.world.player.game_mode.takes_damage() && !self.cam_control.mode.flies() {
+            let body = &self.cam_control.body;
+            let landed = self.health.land(body.impact);
+            let touched = self.health.touch(body.touching(&self.world.current().height), delta as f32);
+            if landed || touched {
+                self.respawn();
+            }
+        }
+
+        let height = &self.world.current().height;
+        // Either half of the player's body touching the portal counts
+        let feet = self.cam_control.body.pos;
+        let in_portal = self.cam_control.mode.interacts() && [0.5, 1.5].iter().any(|d| height.getp(feet + Vector3::new(0., *d, 0.)) == Some(&Block::Portal));
+        if in_portal {
+            self.travel(self.world.current.portal_target());
+        }
+
+        for line in self.console.poll() {
+            self.command(&line);
+        }
+
+        // Creative breaks a block per press of E, survival wears it down while E is held
+        let pressed = std::mem::take(&mut self.break_pressed);
+        let target = match self.cam_control.mode.interacts() && self.screen.is_none() {
+            true => self.world.current().height.ray(self.cam.eye, (self.cam.target - self.cam.eye).normalize() * 5.),
+            false => None
+        };
+        match target {
+            Some((ix, b)) if self.world.player.game_mode.instant_break() => {
+                self.breaking.stop();
+                if pressed && b.hardness().is_some() {
+                    self.break_block(ix);
+                }
+            },
+            Some((ix, b)) if input.key_held(VirtualKeyCode::E) => {
+                if self.breaking.hit(ix, b, self.held_tool(), delta as f32) {
+                    self.break_block(ix);
+                }
+            },
+            _ => self.breaking.stop()
+        }
+        if std::mem::take(&mut self.place_pressed) && self.cam_control.mode.interacts() && self.screen.is_none() {
+            self.place_block();
+        }
+
+        let dim = self.world.current_mut();
+        let changed = dim.falling.update(&mut dim.height, delta as f32);
+        for c in changed.iter() {
+            fluid::notify(&mut dim.ticks, &dim.height, *c);
+        }
+        dim.drop_blocked((self.secs * 1000.) as u64);
+        if !changed.is_empty() {
+            self.height_changed = true;
+        }
+
+        let dim = self.world.current_mut();
+        if !dim.entities.is_empty() {
+            dim.entities.update(&dim.height, delta as f32);
+            if self.cam_control.mode.interacts() {
+                self.world.pick_up(&self.cam_control.body);
+            }
+        }
+
+        // Only the dimension the player is in is loaded enough to tick
+        if self.step == 0 && self.world.current_mut().tick() {
+            self.height_changed = true;
         }
 
         self.secs += delta;
+        self.step = (self.step + 1) % STEPS_PER_TICK;
         self.ctx.queue.write_buffer(&self.time_buffer, 0, bytemuck::cast_slice(&[self.secs as f32,self.secs as f32]));
         
         if self.height_changed {
-            self.ctx.queue.write_buffer(&self.blocks_buffer, 0, bytemuck::cast_slice(&self.height.positions()));
+            // Removing a block can expose more than it hides, so the buffer may need to grow
+            let dim = self.world.current();
+            let instances = dim.height.faces();
+            self.blocks_buffer = fast_buffer(&self.ctx.device, &instances, BufferUsages::VERTEX | BufferUsages::COPY_DST);
+            self.blocks_count = instances.len() as u32;
+            self.height_changed = false;
+        }
+
+        let dim = self.world.current();
+        if self.entities_count > 0 || !dim.falling.entities.is_empty() || !dim.entities.is_empty() {
+            let mut instances: Vec<FaceInstance> = dim.falling.entities.iter().flat_map(|f| f.faces(&dim.height)).collect();
+            instances.extend(dim.entities.items.iter().flat_map(|i| i.faces(&dim.height)));
+            self.entities_buffer = fast_buffer(&self.ctx.device, &instances, BufferUsages::VERTEX);
+            self.entities_count = instances.len() as u32;
+        }
+    }
+    
+    fn travel(&mut self, id: DimensionId) {
+        match self.world.switch(id) {
+            Ok(dim) => {
+                let arrival = dim.arrival();
+                self.cam_control.teleport(arrival);
+                self.height_changed = true;
+            },
+            Err(e) => println!("could not enter {}: {}", id.name(), e)
+        }
+    }
+
+    fn break_block(&mut self, c: (usize, usize, usize)) {
+        let drops = self.world.player.game_mode.drops_items();
+        let r = (self.secs * 1000.) as u64;
+        let dim = self.world.current_mut();
+        let block = *dim.height.get(c).unwrap();
+        if let Some(item) = block.drop().filter(|_| drops) {
+            dim.entities.drop_item(c, ItemStack::new(item, 1), r);
+        }
+        dim.height.set(c, Block::Air, 0);
+        dim.changed(c);
+        self.height_changed = true;
+    }
+
+    // Puts the held block in front of the one looked at, unless the player is standing there
+    fn place_block(&mut self) {
+        let ray = (self.cam.target - self.cam.eye).normalize() * 5.;
+        let c = match self.world.current().height.ray_before(self.cam.eye, ray) {
+            Some(c) => c,
+            None => return
+        };
+        if self.cam_control.body.aabb().overlaps(&physics::Aabb::block((c.0 as i64, c.1 as i64, c.2 as i64))) {
+            return
+        }
+        let block = match self.world.player.take_block() {
+            Some(block) => block,
+            None => return
+        };
+        let dim = self.world.current_mut();
+        dim.height.set(c, block, 0);
+        dim.changed(c);
+        self.height_changed = true;
+    }
+
+    fn set_game_mode(&mut self, mode: GameMode) {
+        self.world.player.game_mode = mode;
+        self.cam_control.can_fly = mode.can_fly();
+        if !mode.can_fly() {
+            self.cam_control.set_mode(MoveMode::Walk);
+        }
+        if !mode.takes_damage() {
+            self.health.reset();
+        }
+    }
+
+    fn respawn(&mut self) {
+        println!("you {}", self.health.last_damage.unwrap_or(Damage::Other).describe());
+        if self.world.current != DimensionId::Overworld {
+            // The overworld is loaded from the start, so it can always be switched back to
+            self.world.switch(DimensionId::Overworld).expect("the overworld is always loaded");
+            self.height_changed = true;
+        }
+        self.cam_control.teleport(eye_at(self.world.level.spawn.unwrap()));
+        self.health.reset();
+    }
+
+    fn command(&mut self, line: &str) {
+        let words: Vec<&str> = line.trim().trim_start_matches('/').split_whitespace().collect();
+        match words[..] {
+            ["dim", name] => match DimensionId::from_name(name) {
+                Some(id) => self.travel(id),
+                None => println!("unknown dimension {}", name)
+            },
+            ["mode", name] => match MoveMode::from_name(name) {
+                Some(mode) => if !self.cam_control.set_mode(mode) {
+                    println!("{} needs creative mode", name);
+                },
+                None => println!("unknown movement mode {}", name)
+            },
+            ["gamemode", name] => match GameMode::from_name(name) {
+                Some(mode) => self.set_game_mode(mode),
+                None => println!("unknown game mode {}", name)
+            },
+            // Highest ledge walked up without jumping, 0 to turn stepping off
+            ["step", height] => match height.parse::<f32>() {
+                Ok(h) if h >= 0. => self.cam_control.body.step_height = h,
+                _ => println!("step height must be a number of blocks, 0 or more")
+            },
+            ["setspawn"] => match self.world.current {
+                DimensionId::Overworld => {
+                    let c = HeightChunk::to_index(self.cam_control.body.pos + Vector3::new(0., 0.5, 0.));
+                    self.world.level.spawn = Some(c);
+                    println!("spawn set to {} {} {}", c.0, c.1, c.2);
+                },
+                _ => println!("the spawn point has to be in the overworld")
+            },
+            ["give", name] | ["give", name, _] => match (Item::from_name(name), words.get(2).map(|n| n.parse::<u32>())) {
+                (Some(item), None) => self.give(ItemStack::new(item, 1)),
+                (Some(item), Some(Ok(count))) => self.give(ItemStack::new(item, count)),
+                (Some(_), Some(Err(_))) => println!("bad count {}", words[2]),
+                (None, _) => println!("unknown item {}", name)
+            },
+            ["health"] => println!("health {}/{}", self.health.current, self.health.max),
+            ["heal"] => self.health.heal(self.health.max),
+            ["inventory"] => {
+                let inventory = &self.world.player.inventory;
+                for (i, stack) in inventory.slots.iter().enumerate() {
+                    if let Some(stack) = stack {
+                        let mark = if i == inventory.selected { "*" } else { " " };
+                        println!("{}{:2} {} x{}", mark, i, stack.item.name(), stack.count);
+                    }
+                }
+            },
+            ["kill"] => {
+                self.health.damage(self.health.current, Damage::Other);
+                self.respawn();
+            },
+            [] => {},
+            _ => println!("unknown command: {}", line.trim())
         }
     }
+
+    fn save(&mut self) {
+        self.close_crafting();
+        self.world.save();
+    }
     
     fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         self.ctx.resize(new_size);
@@ -378,7 +915,7 @@ impl State {
                     view: &view,
                     resolve_target: None,
                     ops: Operations {
-                        load: LoadOp::Clear(self.clear_color),
+                        load: LoadOp::Clear(if self.fog.color[3] > 0. { self.fog.clear_color() } else { self.world.current().sky }),
                         store: true,
                     },
                 }],
@@ -400,11 +937,36 @@ impl State {
             
             rpass.set_bind_group(0, &self.time_bind_group, &[]);
             rpass.set_bind_group(1, &self.cam_bind_group, &[]);
+            rpass.set_bind_group(2, &self.fog_bind_group, &[]);
+            rpass.set_bind_group(3, &self.crack_bind_group, &[]);
             
             rpass.draw_indexed(
                 0..self.block_model.indxs, 
                 0, 
-                0..self.height.positions().len() as u32);
+                0..self.blocks_count);
+
+            if self.entities_count > 0 {
+                rpass.set_vertex_buffer(1, self.entities_buffer.slice(..));
+                rpass.draw_indexed(0..self.block_model.indxs, 0, 0..self.entities_count);
+            }
+            }
+
+            if self.screen.is_some() {
+                let mut rpass = encoder.begin_render_pass(&RenderPassDescriptor {
+                    label: Some("UI Pass"),
+                    color_attachments: &[RenderPassColorAttachment {
+                        view: &view,
+                        resolve_target: None,
+                        ops: Operations {
+                            load: LoadOp::Load,
+                            store: true,
+                        },
+                    }],
+                    depth_stencil_attachment: None,
+                });
+                rpass.set_pipeline(&self.ui_pipe);
+                rpass.set_vertex_buffer(0, self.ui_buffer.slice(..));
+                rpass.draw(0..self.ui_count, 0..1);
             }
             
             self.ctx.queue.submit(std::iter::once(encoder.finish()));
@@ -414,8 +976,80 @@ impl State {
         }
     }
     
+    // Eye position of a player whose feet are in block c
+    fn eye_at(c: (usize, usize, usize)) -> cgmath::Point3<f32> {
+        cgmath::Point3::new(c.0 as f32, c.1 as f32 - 0.5 + physics::EYE_HEIGHT, c.2 as f32)
+    }
+    
+    // --spawn <x>,<z> looks for a spawn point near there instead of the origin
+    fn spawn_from_args(args: &[String]) -> Option<(usize, usize)> {
+        let value = flag(args, "--spawn")?.to_str()?;
+        let (x, z) = value.split_once(',')?;
+        return Some((x.trim().parse().ok()?, z.trim().parse().ok()?))
+    }
+    
+    fn flag<'a>(args: &'a [String], name: &str) -> Option<&'a Path> {
+        args.iter().position(|a| a == name).and_then(|i| args.get(i + 1)).map(Path::new)
+    }
+    
+    // The recipes in the recipes directory are built into the game, so it doesn't need to be
+    // run from anywhere in particular
+    const BUILT_IN_RECIPES: [&str; 2] = [include_str!("../recipes/blocks.txt"), include_str!("../recipes/tools.txt")];
+
+    fn built_in_recipes() -> std::io::Result<RecipeBook> {
+        let mut book = RecipeBook::default();
+        for text in BUILT_IN_RECIPES {
+            book.recipes.extend(RecipeBook::from_text(text)?.recipes);
+        }
+        return Ok(book)
+    }
+
+    // --recipes <dir> reads recipes from there instead
+    fn recipes_from_args(args: &[String]) -> RecipeBook {
+        let loaded = match flag(args, "--recipes") {
+            Some(dir) => RecipeBook::load_dir(dir),
+            None => built_in_recipes()
+        };
+        match loaded {
+            Ok(recipes) => recipes,
+            Err(e) => {
+                eprintln!("could not load recipes, nothing can be crafted: {}", e);
+                RecipeBook::default()
+            }
+        }
+    }
+    
+    fn fail(msg: &str) -> ! {
+        eprintln!("{}", msg);
+        std::process::exit(1)
+    }
+    
+    // --heightmap <png> [--colormap <png>] loads terrain from images instead of noise
+    fn generator_from_args(args: &[String], seed: u32) -> Box<dyn Generator> {
+        match flag(args, "--heightmap") {
+            Some(path) => {
+                let mut map = Heightmap::load(path).unwrap_or_else(|e| fail(&format!("{}: {}", path.display(), e)));
+                if let Some(colors) = flag(args, "--colormap") {
+                    map = map.with_colors(colors).unwrap_or_else(|e| fail(&format!("{}: {}", colors.display(), e)));
+                }
+                Box::new(map)
+            },
+            None => Box::new(Terrain::new(seed))
+        }
+    }
+    
     fn main() {
         env_logger::init();
+        let args: Vec<String> = std::env::args().collect();
+        // --world <dir> loads the world from dir if it was saved there, and saves it on exit
+        let world_dir = flag(&args, "--world").map(Path::to_path_buf);
+        // --seed <n> picks the seed of a new world
+        let seed = match flag(&args, "--seed") {
+            Some(seed) => seed.to_str().and_then(|s| s.parse().ok()).unwrap_or_else(|| fail("--seed expects a number")),
+            None => 0
+        };
+        let world = World::new(world_dir, seed, |seed| generator_from_args(&args, seed))
+            .unwrap_or_else(|e| fail(&format!("could not load the world: {}", e)));
         let event_loop = EventLoop::new();
         let window = WindowBuilder::new()
         .with_title("Copyright Friedrich Hohensee")
@@ -424,10 +1058,11 @@ impl State {
         window.set_cursor_visible(true);
         window.set_cursor_grab(true).unwrap();
         
-        let mut state = pollster::block_on(State::new(&window));
+        let mut state = pollster::block_on(State::new(&window, world, spawn_from_args(&args), recipes_from_args(&args)));
         let mut input = WinitInputHelper::new();
         
         let mut prev = SystemTime::now();
+        let mut acc = 0.;
         let mut mouse_motion: (f64, f64) = (0.,0.);
         event_loop.run(move |event, _, control_flow| {
             match event {
@@ -482,6 +1117,7 @@ impl State {
                     // request it.
                     window.request_redraw();
                 },
+                Event::LoopDestroyed => state.save(),
                 _ => {}
             };
 
@@ -489,12 +1125,29 @@ impl State {
                 let duration = SystemTime::now().duration_since(prev).unwrap();
                 let delta = duration.as_secs() as f64
                     + duration.subsec_nanos() as f64 * 1e-9;
-                state.update(
-                    delta, &window, &input, mouse_motion
-                );
+                state.frame(&window, &input, mouse_motion);
+                acc += delta.min(MAX_FRAME);
+                while acc >= STEP {
+                    state.update(&input);
+                    acc -= STEP;
+                }
+                state.interpolate((acc / STEP) as f32);
                 prev = SystemTime::now();
 
                 mouse_motion = (0.,0.);
             }
         });
-    }
\ No newline at end of file
+    }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_recipes_load() {
+        let book = built_in_recipes().unwrap();
+        let ladder = Item::from_name("ladder").unwrap();
+        assert!(book.recipes.iter().any(|r| r.result.item == ladder));
+        assert_eq!(book.recipes.len(), 6);
+    }
+}
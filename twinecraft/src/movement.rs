@@ -0,0 +1,64 @@
+// How the player moves through the world
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MoveMode {
+    // Gravity and collision
+    Walk,
+    // Creative flight, still bumping into blocks
+    Fly,
+    // Flight through blocks
+    Noclip,
+    // Flight through blocks that leaves the world alone: no breaking blocks, no portals
+    Spectator
+}
+impl MoveMode {
+    pub const ALL: [MoveMode; 4] = [MoveMode::Walk, MoveMode::Fly, MoveMode::Noclip, MoveMode::Spectator];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MoveMode::Walk => "walk",
+            MoveMode::Fly => "fly",
+            MoveMode::Noclip => "noclip",
+            MoveMode::Spectator => "spectator"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<MoveMode> {
+        Self::ALL.iter().copied().find(|m| m.name() == name)
+    }
+
+    // The one after this in ALL, for switching modes with a single key
+    pub fn next(self) -> MoveMode {
+        let i = Self::ALL.iter().position(|m| *m == self).unwrap();
+        return Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+
+    // Horizontal speed in blocks per second
+    pub fn speed(self) -> f32 {
+        match self {
+            MoveMode::Walk => 10.,
+            MoveMode::Fly | MoveMode::Noclip => 15.,
+            MoveMode::Spectator => 25.
+        }
+    }
+
+    // Speed going up with Space and down with LShift, for modes that fly
+    pub fn vertical_speed(self) -> f32 {
+        match self {
+            MoveMode::Walk => 0.,
+            MoveMode::Fly | MoveMode::Noclip => 10.,
+            MoveMode::Spectator => 15.
+        }
+    }
+
+    pub fn flies(self) -> bool {
+        self != MoveMode::Walk
+    }
+
+    pub fn collides(self) -> bool {
+        matches!(self, MoveMode::Walk | MoveMode::Fly)
+    }
+
+    pub fn interacts(self) -> bool {
+        self != MoveMode::Spectator
+    }
+}
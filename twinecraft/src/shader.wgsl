@@ -1,15 +1,29 @@
 // Vertex shader
 
 struct VertexInput {
-    [[location(0)]] position: vec3<f32>;
-    [[location(1)]] color: vec3<f32>;
-    [[location(2)]] worldpos: vec3<f32>;
+    [[location(0)]] corner: vec2<f32>;
+    [[location(1)]] worldpos: vec3<f32>;
+    [[location(2)]] face: f32;
+    [[location(3)]] block_color: vec3<f32>;
+    [[location(4)]] block_height: f32;
+    // Sky light, then red, green and blue block light
+    [[location(5)]] light: vec4<f32>;
+    // Ambient occlusion at corners (0,0), (1,0), (1,1), (0,1)
+    [[location(6)]] ao: vec4<f32>;
+    // Scale of the cube around its centre, 1 for blocks in the world
+    [[location(7)]] size: f32;
+    // 1 if the quad is split along its 1-3 diagonal, see chunk::mesh::flips
+    [[location(8)]] flip: f32;
 };
 
 struct VertexOutput {
     [[builtin(position)]] clip_position: vec4<f32>;
     [[location(0)]] color: vec3<f32>;
     [[location(1)]] worldpos: vec3<f32>;
+    [[location(2)]] light: vec4<f32>;
+    [[location(3)]] ao: f32;
+    [[location(4)]] pos: vec3<f32>;
+    [[location(5)]] uv: vec2<f32>;
 };
 
 [[block]]
@@ -33,16 +47,73 @@ struct Camera {
 [[group(1), binding(0)]]
 var<uniform> camera: Camera;
 
+[[block]]
+struct Fog {
+    // Thickness in w, 0 when the camera isn't in a liquid
+    color: vec4<f32>;
+    eye: vec4<f32>;
+};
+[[group(2), binding(0)]]
+var<uniform> fog: Fog;
+
+[[block]]
+struct Crack {
+    // Block being broken, with the crack stage in w or -1 if there is none
+    target: vec4<f32>;
+};
+[[group(3), binding(0)]]
+var<uniform> crack: Crack;
+[[group(3), binding(1)]]
+var crack_texture: texture_2d<f32>;
+[[group(3), binding(2)]]
+var crack_sampler: sampler;
+// Crack stages are stacked top to bottom in crack_texture
+let CRACK_STAGES: f32 = 10.0;
+
+// Faces are numbered +x, -x, +y, -y, +z, -z as in chunk::mesh::FACES
+fn face_corner(face: i32, u: f32, v: f32) -> vec3<f32> {
+    let a = u - 0.5;
+    let b = v - 0.5;
+    if (face == 0) { return vec3<f32>(0.5, a, b); }
+    if (face == 1) { return vec3<f32>(-0.5, a, b); }
+    if (face == 2) { return vec3<f32>(a, 0.5, b); }
+    if (face == 3) { return vec3<f32>(a, -0.5, b); }
+    if (face == 4) { return vec3<f32>(a, b, 0.5); }
+    return vec3<f32>(a, b, -0.5);
+}
+
+// Fixed shading per direction so neighbouring faces stay distinguishable
+fn face_shade(face: i32) -> f32 {
+    if (face == 2) { return 1.0; }
+    if (face == 3) { return 0.5; }
+    if (face < 2) { return 0.8; }
+    return 0.65;
+}
+
 [[stage(vertex)]]
 fn vs_main(
     model: VertexInput
 ) -> VertexOutput {
     var out: VertexOutput;
-    out.color = model.color;
-    var pos: vec3<f32> = model.position + model.worldpos;
-    pos = pos * 1.;
+    let face = i32(model.face + 0.5);
+    out.color = model.block_color * face_shade(face);
+    // The quad is split along its 0-2 diagonal. Flipped quads move every vertex one corner
+    // on, so the split runs along the other one instead.
+    var corner: vec2<f32> = model.corner;
+    if (model.flip > 0.5) {
+        corner = vec2<f32>(1.0 - model.corner.y, model.corner.x);
+    }
+    out.ao = mix(mix(model.ao.x, model.ao.y, corner.x), mix(model.ao.w, model.ao.z, corner.x), corner.y);
+    // Liquids that have flowed away from their source sit lower in the block
+    var local: vec3<f32> = face_corner(face, corner.x, corner.y);
+    local.y = (local.y + 0.5) * model.block_height - 0.5;
+    local = local * model.size;
+    var pos: vec3<f32> = local + model.worldpos;
     out.clip_position = camera.view_proj * vec4<f32>(pos, 1.0);
+    out.pos = pos;
+    out.uv = corner;
     out.worldpos = model.worldpos;
+    out.light = model.light;
     return out;
 }
 
@@ -50,5 +121,23 @@ fn vs_main(
 
 [[stage(fragment)]]
 fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
-    return vec4<f32>(in.color,1.);
-}
\ No newline at end of file
+    // Light levels are linear 0..1, squaring them gives a steeper falloff into the dark.
+    // Block light is screened over sky light, so colored light shows most where the sky doesn't reach.
+    let sky = vec3<f32>(in.light.x * in.light.x);
+    let block = in.light.yzw * in.light.yzw;
+    let light = vec3<f32>(1.0) - (vec3<f32>(1.0) - sky) * (vec3<f32>(1.0) - block);
+    let occlusion = 0.4 + 0.6 * in.ao;
+    var color: vec3<f32> = in.color * max(light, vec3<f32>(0.05)) * occlusion;
+    // Cracks over the block being broken
+    let stage = max(crack.target.w, 0.0);
+    let cracks = textureSample(crack_texture, crack_sampler, vec2<f32>(in.uv.x, (stage + clamp(in.uv.y, 0.0, 0.999)) / CRACK_STAGES));
+    if (crack.target.w >= 0.0 && distance(in.worldpos, crack.target.xyz) < 0.1) {
+        color = mix(color, cracks.rgb, cracks.a);
+    }
+    // Inside a liquid everything is tinted, and fades into the fog with distance
+    if (fog.color.w > 0.0) {
+        let thickness = 1.0 - exp(-fog.color.w * distance(in.pos, fog.eye.xyz));
+        color = mix(color, fog.color.rgb, 0.3 + 0.7 * thickness);
+    }
+    return vec4<f32>(color, 1.);
+}
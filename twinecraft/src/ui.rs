@@ -0,0 +1,207 @@
+use wgpu::*;
+use chunk::crafting::{RecipeBook, GRID};
+use chunk::inventory::{Inventory, HOTBAR, SLOTS};
+use chunk::item::ItemStack;
+
+// Size of a slot and the space between slots, in pixels
+const SLOT: f32 = 40.;
+const GAP: f32 = 4.;
+const PITCH: f32 = SLOT + GAP;
+// Space around an item inside its slot
+const INSET: f32 = 6.;
+const COUNT_BAR: f32 = 3.;
+
+const SHADE: [f32; 4] = [0., 0., 0., 0.5];
+const PANEL: [f32; 4] = [0.2, 0.2, 0.22, 0.95];
+const EMPTY: [f32; 4] = [0.35, 0.35, 0.38, 1.];
+const SELECTED: [f32; 4] = [0.6, 0.6, 0.65, 1.];
+const COUNT: [f32; 4] = [1., 1., 1., 0.9];
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct UiVertex {
+    // Position in clip space
+    pos: [f32; 2],
+    color: [f32; 4]
+}
+impl UiVertex {
+    pub fn desc<'a>() -> VertexBufferLayout<'a> {
+        VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as BufferAddress,
+            step_mode: VertexStepMode::Vertex,
+            attributes: &[
+            VertexAttribute {
+                offset: 0,
+                shader_location: 0,
+                format: VertexFormat::Float32x2,
+            },
+            VertexAttribute {
+                offset: std::mem::size_of::<[f32; 2]>() as BufferAddress,
+                shader_location: 1,
+                format: VertexFormat::Float32x4,
+            }
+            ]
+        }
+    }
+}
+
+// Solid rectangles in pixels from the top left of a window, turned into triangles
+struct Quads {
+    size: (f32, f32),
+    verts: Vec<UiVertex>
+}
+impl Quads {
+    fn rect(&mut self, x: f32, y: f32, w: f32, h: f32, color: [f32; 4]) {
+        let p = |x: f32, y: f32| UiVertex { pos: [x / self.size.0 * 2. - 1., 1. - y / self.size.1 * 2.], color };
+        let (a, b, c, d) = (p(x, y), p(x + w, y), p(x + w, y + h), p(x, y + h));
+        self.verts.extend_from_slice(&[a, d, c, a, c, b]);
+    }
+
+    // An item as a square of its color, with a bar along the bottom showing how full the stack is
+    fn stack(&mut self, x: f32, y: f32, stack: ItemStack, alpha: f32) {
+        let c = stack.item.color();
+        let size = SLOT - 2. * INSET;
+        self.rect(x + INSET, y + INSET, size, size, [c[0], c[1], c[2], alpha]);
+        let max = stack.item.max_stack();
+        if max > 1 {
+            let full = stack.count.min(max) as f32 / max as f32;
+            self.rect(x + INSET, y + SLOT - INSET, size * full, COUNT_BAR, COUNT);
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Slot {
+    Grid(usize),
+    Result,
+    Inventory(usize)
+}
+
+// Everything the screen was last drawn from
+#[derive(Clone, PartialEq)]
+struct Drawn {
+    size: (f32, f32),
+    grid: [Option<ItemStack>; GRID * GRID],
+    cursor: Option<ItemStack>,
+    inventory: Inventory,
+    // Only while a stack is held, since nothing else follows the mouse
+    mouse: Option<(f32, f32)>
+}
+
+// The crafting grid opened over the game, with the player's inventory below it. Clicking a
+// slot swaps its stack with the one held by the mouse, or tops it up with the same item.
+pub struct CraftingScreen {
+    pub grid: [Option<ItemStack>; GRID * GRID],
+    // The stack picked up with the mouse
+    pub cursor: Option<ItemStack>,
+    drawn: Option<Drawn>
+}
+impl CraftingScreen {
+    pub fn new() -> Self {
+        Self { grid: [None; GRID * GRID], cursor: None, drawn: None }
+    }
+
+    // Top left corner of every slot for a window of size pixels. The grid and its result are
+    // on top, then main storage and the hotbar.
+    fn layout(size: (f32, f32)) -> Vec<(Slot, f32, f32)> {
+        let left = (size.0 - (HOTBAR as f32 * PITCH - GAP)) / 2.;
+        let top = (size.1 - (8.5 * PITCH - GAP)) / 2.;
+        let mut slots = Vec::new();
+        for i in 0..GRID * GRID {
+            slots.push((Slot::Grid(i), left + (1 + i % GRID) as f32 * PITCH, top + (i / GRID) as f32 * PITCH));
+        }
+        slots.push((Slot::Result, left + 6. * PITCH, top + PITCH));
+        for i in HOTBAR..SLOTS {
+            let j = i - HOTBAR;
+            slots.push((Slot::Inventory(i), left + (j % HOTBAR) as f32 * PITCH, top + (4 + j / HOTBAR) as f32 * PITCH));
+        }
+        for i in 0..HOTBAR {
+            slots.push((Slot::Inventory(i), left + i as f32 * PITCH, top + 7.5 * PITCH));
+        }
+        return slots
+    }
+
+    pub fn slot_at(size: (f32, f32), mouse: (f32, f32)) -> Option<Slot> {
+        let inside = |x: f32, y: f32| mouse.0 >= x && mouse.0 < x + SLOT && mouse.1 >= y && mouse.1 < y + SLOT;
+        Self::layout(size).into_iter().find(|(_, x, y)| inside(*x, *y)).map(|(slot, _, _)| slot)
+    }
+
+    pub fn click(&mut self, slot: Slot, inventory: &mut Inventory, recipes: &RecipeBook) {
+        let cell = match slot {
+            Slot::Grid(i) => &mut self.grid[i],
+            Slot::Inventory(i) => &mut inventory.slots[i],
+            Slot::Result => {
+                let made = match recipes.find(&self.grid, GRID) {
+                    Some(recipe) => recipe.result,
+                    None => return
+                };
+                // Crafting again only adds to a held stack of the same item with room for it
+                let count = match self.cursor {
+                    None => 0,
+                    Some(held) if held.item == made.item && held.count + made.count <= made.item.max_stack() => held.count,
+                    Some(_) => return
+                };
+                recipes.craft(&mut self.grid, GRID);
+                self.cursor = Some(ItemStack::new(made.item, count + made.count));
+                return
+            }
+        };
+        match (cell.as_mut(), self.cursor.as_mut()) {
+            (Some(into), Some(held)) if into.item == held.item => {
+                let moved = held.count.min(into.item.max_stack().saturating_sub(into.count));
+                into.count += moved;
+                held.count -= moved;
+                if held.count == 0 {
+                    self.cursor = None;
+                }
+            },
+            _ => std::mem::swap(cell, &mut self.cursor)
+        }
+    }
+
+    // Puts everything on the screen back in the inventory. Returns what didn't fit.
+    pub fn close(self, inventory: &mut Inventory) -> Vec<ItemStack> {
+        self.grid.iter().chain(std::iter::once(&self.cursor)).flatten().filter_map(|s| inventory.add(*s)).collect()
+    }
+
+    // The screen's vertices, if anything on it changed since they were last asked for
+    pub fn redraw(&mut self, size: (f32, f32), inventory: &Inventory, recipes: &RecipeBook, mouse: Option<(f32, f32)>) -> Option<Vec<UiVertex>> {
+        let drawn = Drawn { size, grid: self.grid, cursor: self.cursor, inventory: inventory.clone(), mouse: self.cursor.and(mouse) };
+        if self.drawn.as_ref() == Some(&drawn) {
+            return None
+        }
+        self.drawn = Some(drawn);
+        return Some(self.vertices(size, inventory, recipes, mouse))
+    }
+
+    pub fn vertices(&self, size: (f32, f32), inventory: &Inventory, recipes: &RecipeBook, mouse: Option<(f32, f32)>) -> Vec<UiVertex> {
+        let mut quads = Quads { size, verts: Vec::new() };
+        quads.rect(0., 0., size.0, size.1, SHADE);
+        let slots = Self::layout(size);
+        let (x0, y0) = (slots.iter().map(|s| s.1).fold(f32::MAX, f32::min), slots.iter().map(|s| s.2).fold(f32::MAX, f32::min));
+        let (x1, y1) = (slots.iter().map(|s| s.1).fold(f32::MIN, f32::max), slots.iter().map(|s| s.2).fold(f32::MIN, f32::max));
+        quads.rect(x0 - 2. * GAP, y0 - 2. * GAP, x1 - x0 + SLOT + 4. * GAP, y1 - y0 + SLOT + 4. * GAP, PANEL);
+
+        let made = recipes.find(&self.grid, GRID).map(|r| r.result);
+        for (slot, x, y) in slots {
+            let color = if slot == Slot::Inventory(inventory.selected) { SELECTED } else { EMPTY };
+            quads.rect(x, y, SLOT, SLOT, color);
+            let stack = match slot {
+                Slot::Grid(i) => self.grid[i],
+                Slot::Inventory(i) => inventory.slots[i],
+                Slot::Result => {
+                    // An arrow pointing from the grid to what it makes
+                    quads.rect(x - 1.5 * PITCH + GAP, y + SLOT / 2. - GAP / 2., PITCH, GAP, EMPTY);
+                    made
+                }
+            };
+            if let Some(stack) = stack {
+                quads.stack(x, y, stack, 1.);
+            }
+        }
+        if let (Some(held), Some(mouse)) = (self.cursor, mouse) {
+            quads.stack(mouse.0 - SLOT / 2., mouse.1 - SLOT / 2., held, 0.9);
+        }
+        return quads.verts
+    }
+}